@@ -6,7 +6,7 @@ use std::time::Duration;
 
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use keyboard_query::{DeviceQuery, DeviceState};
-use light_control::battery_voltage_to_capacity::battery_voltage_to_capacity;
+use light_control::battery::BatteryModel;
 use tokio::time::Instant;
 use tui::backend::CrosstermBackend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
@@ -15,11 +15,14 @@ use tui::text::{Span, Spans};
 use tui::widgets::{Block, BorderType, Borders, Paragraph};
 use tui::{Frame, Terminal};
 
-use light_control::bsp::adc::Sensors;
+use light_control::bsp::adc::{Decidegrees, Millivolts, SensorError, Sensors};
 use light_control::bsp::led::Led;
 use light_control::bsp::rgb::{Rgb, BLUE, GREEN, RED};
 use light_control::control::LightControl;
 use light_control::edt::{Event, EDT};
+use light_control::profile::COMMUTE;
+use light_control::record_store::MemoryStorage;
+use sensor_replay::CsvAccelerometer;
 
 use crate::dummy_led::DummyLed;
 use crate::dummy_rgb::DummyRgb;
@@ -37,12 +40,12 @@ struct DummySensors {
 }
 
 impl Sensors for DummySensors {
-    fn battery_voltage(&self, _high_percentage: u32, _low_percentage: u32) -> u32 {
-        self.battery.get()
+    fn battery_voltage(&self) -> Result<Millivolts, SensorError> {
+        Ok(Millivolts(self.battery.get()))
     }
 
-    fn temp(&self) -> i32 {
-        self.temp.get()
+    fn temp(&self) -> Result<Decidegrees, SensorError> {
+        Ok(Decidegrees::from_celsius(self.temp.get()))
     }
 }
 
@@ -56,9 +59,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         battery: Cell::new(8000),
         temp: Cell::new(20),
     };
+    let storage = MemoryStorage::create();
+    // recorded accelerometer samples, see CsvAccelerometer for the format
+    let recording = match std::env::args().nth(1) {
+        Some(path) => Some(std::fs::read_to_string(path)?),
+        None => None,
+    };
+    let clock = || edt.now();
+    let accelerometer = recording
+        .as_deref()
+        .map(|csv| CsvAccelerometer::create(csv, &clock));
     let light_control = LightControl::new(
-        plus_pin, minus_pin, toggle_pin, &led, &led_high, &rgb, &edt, &sensors,
+        plus_pin, minus_pin, toggle_pin, &led, &led_high, &rgb, &edt, &sensors, &storage, COMMUTE,
     );
+    if let Some(accelerometer) = &accelerometer {
+        light_control.set_accelerometer(accelerometer);
+    }
     light_control.start();
     light_control.jump_start();
 
@@ -68,25 +84,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     terminal.clear()?;
 
     let kbd = DeviceState::new();
-    let prev_drawn_state: Cell<(u32, u32, u8, i32, u32)> = Cell::new((0, 0, 0, 20, 8000));
+    let prev_drawn_state: Cell<(u32, u32, u8, i32, u32, Option<u32>)> =
+        Cell::new((0, 0, 0, 20, 8000, None));
     let mut since_last_temp_check = 0;
     loop {
         if kbd.get_keys().contains(&KEY_CODE_ESC) {
             break;
         }
 
-        match kbd.get_keys().iter().next() {
-            Some(&KEY_CODE_1) => sensors.battery.set(battery_capacity(2)),
-            Some(&KEY_CODE_2) => sensors.battery.set(battery_capacity(4)),
-            Some(&KEY_CODE_3) => sensors.battery.set(battery_capacity(7)),
-            Some(&KEY_CODE_4) => sensors.battery.set(battery_capacity(10)),
-            Some(&KEY_CODE_5) => sensors.battery.set(battery_capacity(15)),
-            Some(&KEY_CODE_6) => sensors.battery.set(battery_capacity(20)),
-            Some(&KEY_CODE_7) => sensors.battery.set(battery_capacity(30)),
-            Some(&KEY_CODE_8) => sensors.battery.set(battery_capacity(40)),
-            Some(&KEY_CODE_9) => sensors.battery.set(battery_capacity(80)),
-            Some(&KEY_CODE_0) => sensors.battery.set(battery_capacity(100)),
-            _ => {}
+        let battery = match kbd.get_keys().iter().next() {
+            Some(&KEY_CODE_1) => Some(battery_capacity(2)),
+            Some(&KEY_CODE_2) => Some(battery_capacity(4)),
+            Some(&KEY_CODE_3) => Some(battery_capacity(7)),
+            Some(&KEY_CODE_4) => Some(battery_capacity(10)),
+            Some(&KEY_CODE_5) => Some(battery_capacity(15)),
+            Some(&KEY_CODE_6) => Some(battery_capacity(20)),
+            Some(&KEY_CODE_7) => Some(battery_capacity(30)),
+            Some(&KEY_CODE_8) => Some(battery_capacity(40)),
+            Some(&KEY_CODE_9) => Some(battery_capacity(80)),
+            Some(&KEY_CODE_0) => Some(battery_capacity(100)),
+            _ => None,
+        };
+        if let Some(battery) = battery {
+            sensors.battery.set(battery);
+            // swapped battery, counted charge does not apply anymore
+            light_control.reset_fuel_gauge();
         }
 
         match edt.poll() {
@@ -103,12 +125,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
 
                 let start = Instant::now();
-                let state_to_draw: (u32, u32, u8, i32, u32) = (
+                let state_to_draw: (u32, u32, u8, i32, u32, Option<u32>) = (
                     led.get(),
                     led_high.get(),
                     rgb.get_rgb(),
                     sensors.temp.get(),
                     sensors.battery.get(),
+                    light_control.current_runtime(),
                 );
                 if prev_drawn_state.get() != state_to_draw {
                     draw_tui(
@@ -118,6 +141,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         rgb.get_rgb(),
                         sensors.temp.get(),
                         sensors.battery.get(),
+                        light_control.current_runtime(),
                     )?;
                 }
                 prev_drawn_state.set(state_to_draw);
@@ -144,6 +168,7 @@ fn draw_tui(
     rgb: u8,
     temp: i32,
     bat: u32,
+    runtime: Option<u32>,
 ) -> io::Result<()> {
     terminal.draw(|rect| {
         let vertical_layout = Layout::default()
@@ -154,7 +179,7 @@ fn draw_tui(
                     Constraint::Length(3),
                     Constraint::Length(3),
                     Constraint::Length(3),
-                    Constraint::Length(4),
+                    Constraint::Length(5),
                     Constraint::Length(4),
                     Constraint::Length(4),
                 ]
@@ -171,6 +196,7 @@ fn draw_tui(
             led_high,
             temp,
             bat,
+            runtime,
             rect,
             vertical_iter.next().unwrap(),
         );
@@ -194,6 +220,7 @@ fn render_values_paragraph(
     led_high: u32,
     temp: i32,
     bat: u32,
+    runtime: Option<u32>,
     rect: &mut Frame<CrosstermBackend<Stdout>>,
     area: Rect,
 ) {
@@ -203,8 +230,12 @@ fn render_values_paragraph(
         Spans::from(Span::raw(format!("Temp: {}", temp))),
         Spans::from(Span::raw(format!(
             "Bat:  {}",
-            battery_voltage_to_capacity(bat)
+            BatteryModel::default().capacity(bat)
         ))),
+        Spans::from(Span::raw(match runtime {
+            Some(minutes) => format!("Run:  {} min", minutes),
+            None => "Run:  -".to_string(),
+        })),
     ])
     .alignment(Alignment::Left);
     rect.render_widget(values_paragraph, area);
@@ -1,4 +1,4 @@
-use light_control::battery_voltage_to_capacity::battery_voltage_to_capacity;
+use light_control::battery::BatteryModel;
 
 /// ## Given current temperature and led power, calculates radiator after (some) time period
 ///
@@ -26,8 +26,9 @@ pub fn calculate_temperature(led_low: u32, led_high: u32, prev_temp: i32) -> i32
 }
 
 pub fn battery_capacity(capacity: u32) -> u32 {
-    return (6000..8500)
+    let model = BatteryModel::default();
+    return (model.empty_mv()..model.full_mv() + 100)
         .step_by(100)
-        .find(|voltage| battery_voltage_to_capacity(*voltage) >= capacity)
+        .find(|voltage| model.capacity(*voltage) >= capacity)
         .unwrap_or(0);
 }
@@ -3,6 +3,8 @@ MEMORY
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   /* These values correspond to the NRF52840 with Softdevices S140 7.0.1 */
   /* https://infocenter.nordicsemi.com/index.jsp?topic=%2Fsds_s140%2FSDS%2Fs1xx%2Fmem_usage%2Fmem_resource_reqs.html&cp=5_7_4_0_13_0_0 */
-  FLASH : ORIGIN = 0x27000, LENGTH = 868K
+  /* Application ends where the user data of the Adafruit bootloader starts (0xED000), */
+  /* rusty_light keeps its settings in the first two pages of it */
+  FLASH : ORIGIN = 0x27000, LENGTH = 792K
   RAM : ORIGIN = 0x20000008, LENGTH = 0x3fff8
 }
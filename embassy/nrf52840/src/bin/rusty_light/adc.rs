@@ -0,0 +1,64 @@
+use core::cell::Cell;
+
+use embassy_nrf::saadc::Saadc;
+use embassy_time::{Duration, Timer};
+
+use light_control::bsp::adc::{Decidegrees, Millivolts, SensorError, Sensors};
+use light_control::ntc::{Ntc, NTC_10K_B3435};
+
+/// Channels of the [Saadc], in the order they were configured
+const BATTERY_CHANNEL: usize = 0;
+const TEMP_CHANNEL: usize = 1;
+
+const SAMPLES: i32 = 16;
+/// Internal 0.6 V reference with gain 1/6, 12 bit
+const FULL_SCALE_MV: i32 = 3600;
+const RESOLUTION: i32 = 4096;
+/// Thermistor on the LED board, open and shorted ones are reported as faults
+const NTC: Ntc = NTC_10K_B3435;
+
+/// SAADC can only be sampled asynchronously, so [AdcSensors::measure_every] runs alongside
+/// [LightControl] and [Sensors] hand out the latest measurements.
+pub struct AdcSensors {
+    pub r_pull_up: u32,
+    pub r_pull_down: u32,
+    pub vin_mv: Cell<u32>,
+    pub temp_mv: Cell<u32>,
+}
+
+impl AdcSensors {
+    pub async fn measure(&self, saadc: &mut Saadc<'_, 2>) {
+        let mut sum = [0i32; 2];
+        for _ in 0..SAMPLES {
+            let mut buf = [0i16; 2];
+            saadc.sample(&mut buf).await;
+            sum[BATTERY_CHANNEL] += buf[BATTERY_CHANNEL] as i32;
+            sum[TEMP_CHANNEL] += buf[TEMP_CHANNEL] as i32;
+        }
+        self.vin_mv.set(to_mv(sum[BATTERY_CHANNEL] / SAMPLES));
+        self.temp_mv.set(to_mv(sum[TEMP_CHANNEL] / SAMPLES));
+    }
+
+    pub async fn measure_every(&self, saadc: &mut Saadc<'_, 2>, period: Duration) -> ! {
+        loop {
+            Timer::after(period).await;
+            self.measure(saadc).await;
+        }
+    }
+}
+
+impl Sensors for AdcSensors {
+    fn battery_voltage(&self) -> Result<Millivolts, SensorError> {
+        let v_bat: u32 = self.vin_mv.get() * (self.r_pull_up + self.r_pull_down) / self.r_pull_down;
+        Ok(Millivolts(v_bat))
+    }
+
+    fn temp(&self) -> Result<Decidegrees, SensorError> {
+        NTC.temp(Millivolts(self.temp_mv.get()))
+    }
+}
+
+/// Single ended samples may be slightly negative due to the offset
+fn to_mv(sample: i32) -> u32 {
+    (sample.max(0) * FULL_SCALE_MV / RESOLUTION) as u32
+}
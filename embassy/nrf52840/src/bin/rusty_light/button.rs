@@ -0,0 +1,13 @@
+use embassy_nrf::gpio::{AnyPin, Input};
+
+use light_control::bsp::pin::Pin;
+
+pub struct PullUpButton<'d> {
+    pub pin: Input<'d, AnyPin>,
+}
+
+impl<'d> Pin for PullUpButton<'d> {
+    fn is_down(&self) -> bool {
+        self.pin.is_low()
+    }
+}
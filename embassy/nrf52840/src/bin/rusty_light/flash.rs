@@ -0,0 +1,56 @@
+use core::cell::RefCell;
+
+use embassy_nrf::nvmc::{Nvmc, PAGE_SIZE};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+use light_control::bsp::storage::{FlashPages, StorageError};
+
+/// First pages of the user data area of the Adafruit bootloader, excluded from FLASH in memory.x
+pub const STORAGE_FIRST_PAGE: usize = 0xED000 / PAGE_SIZE;
+pub const STORAGE_PAGES: usize = 2;
+
+/// Pages of the internal flash used by [RecordStore]
+pub struct NrfFlash<'d> {
+    pub nvmc: RefCell<Nvmc<'d>>,
+    pub first_page: usize,
+    pub pages: usize,
+}
+
+impl<'d> NrfFlash<'d> {
+    fn address(&self, page: usize, offset: usize) -> u32 {
+        ((self.first_page + page) * PAGE_SIZE + offset) as u32
+    }
+}
+
+impl<'d> FlashPages for NrfFlash<'d> {
+    fn page_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn page_count(&self) -> usize {
+        self.pages
+    }
+
+    fn read(&self, page: usize, offset: usize, buf: &mut [u8]) {
+        let address = self.address(page, offset);
+        // reads are only rejected when out of bounds
+        let _ = self.nvmc.borrow_mut().read(address, buf);
+    }
+
+    /// Programs words, which is the smallest unit of the nRF flash
+    fn write(&self, page: usize, offset: usize, data: &[u8]) -> Result<(), StorageError> {
+        let address = self.address(page, offset);
+        self.nvmc
+            .borrow_mut()
+            .write(address, data)
+            .map_err(|_| StorageError::Flash)
+    }
+
+    fn erase(&self, page: usize) -> Result<(), StorageError> {
+        let from = self.address(page, 0);
+        self.nvmc
+            .borrow_mut()
+            .erase(from, from + PAGE_SIZE as u32)
+            .map_err(|_| StorageError::Flash)
+    }
+}
@@ -0,0 +1,207 @@
+#![no_std]
+#![no_main]
+
+use core::cell::{Cell, RefCell};
+use core::future::pending;
+use core::mem;
+
+use defmt::unwrap;
+use embassy_executor::Spawner;
+use embassy_futures::join::join5;
+use embassy_nrf::gpio::{Input, Level, Output, OutputDrive, Pin, Pull};
+use embassy_nrf::nvmc::Nvmc;
+use embassy_nrf::pwm::{Prescaler, SimplePwm};
+use embassy_nrf::saadc::{ChannelConfig, Saadc};
+use embassy_nrf::wdt::{self, Watchdog};
+use embassy_nrf::{bind_interrupts, pac, peripherals, saadc, usb};
+use embassy_time::{Duration, Timer};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use static_cell::StaticCell;
+
+use light_control::bsp::rgb::Rgb;
+use light_control::console::Console;
+use light_control::control::{Action, LightControl};
+use light_control::edt::EDT;
+use light_control::embassy_runner::run;
+use light_control::profile::COMMUTE;
+use light_control::record_store::RecordStore;
+
+use crate::adc::AdcSensors;
+use crate::button::PullUpButton;
+use crate::flash::{NrfFlash, STORAGE_FIRST_PAGE, STORAGE_PAGES};
+use crate::pwm_led::PwmLed;
+use crate::rgb::GpioRgb;
+use crate::usb::{reset_to_bootloader, usb_builder, usb_task, UsbSerial, MAX_PACKET_SIZE};
+
+use {defmt_rtt as _, panic_probe as _};
+
+mod adc;
+mod button;
+mod flash;
+mod pwm_led;
+mod rgb;
+mod usb;
+
+bind_interrupts!(struct Irqs {
+    USBD => usb::InterruptHandler<peripherals::USBD>;
+    POWER_CLOCK => usb::vbus_detect::InterruptHandler;
+    SAADC => saadc::InterruptHandler;
+});
+
+/// 16 MHz / 16 / 3333 = 300 Hz, driver range is 200-1000 Hz
+const PWM_MAX_DUTY: u16 = 3333;
+const WATCHDOG_TIMEOUT_TICKS: u32 = 32768 * 2;
+
+const SENSORS_PERIOD: Duration = Duration::from_millis(100);
+const WATCHDOG_PERIOD: Duration = Duration::from_millis(500);
+/// Console replies and telemetry are sent in batches
+const SERIAL_FLUSH_PERIOD: Duration = Duration::from_millis(20);
+
+// Pins of the XIAO nRF52840:
+// D0     P0_02        battery voltage divider
+// D1     P0_03        plus button
+// D2     P0_28        minus button
+// D3     P0_29        toggle button
+// D4     P0_04        temperature sensor
+// D6     P1_11        low beam driver
+// D7     P1_12        high beam driver
+// D8-D10 P1_13-P1_15  RGB LED
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    enable_external_osc();
+
+    let mut watchdog_config = wdt::Config::default();
+    watchdog_config.timeout_ticks = WATCHDOG_TIMEOUT_TICKS;
+    let (_watchdog, [mut watchdog]) = match Watchdog::try_new(p.WDT, watchdog_config) {
+        Ok(watchdog) => watchdog,
+        Err(_) => defmt::panic!("Watchdog is already running with a different config"),
+    };
+
+    let mut usb_builder = usb_builder(p.USBD);
+    static STATE: StaticCell<State> = StaticCell::new();
+    let state = STATE.init(State::new());
+    let class = CdcAcmClass::new(&mut usb_builder, state, MAX_PACKET_SIZE as u16);
+    unwrap!(spawner.spawn(usb_task(usb_builder.build())));
+    let (mut sender, mut receiver) = class.split();
+
+    let edt = EDT::create();
+
+    let pwm = SimplePwm::new_2ch(p.PWM0, p.P1_11, p.P1_12);
+    pwm.set_prescaler(Prescaler::Div16);
+    pwm.set_max_duty(PWM_MAX_DUTY);
+    let led_low = PwmLed::create(&pwm, 0);
+    let led_high = PwmLed::create(&pwm, 1);
+
+    let rgb = GpioRgb {
+        r: RefCell::new(Output::new(
+            p.P1_14.degrade(),
+            Level::High,
+            OutputDrive::Standard,
+        )),
+        g: RefCell::new(Output::new(
+            p.P1_13.degrade(),
+            Level::High,
+            OutputDrive::Standard,
+        )),
+        b: RefCell::new(Output::new(
+            p.P1_15.degrade(),
+            Level::High,
+            OutputDrive::Standard,
+        )),
+        state: Cell::new(0),
+    };
+
+    rgb.set_rgb(0);
+
+    let mut saadc = Saadc::new(
+        p.SAADC,
+        Irqs,
+        saadc::Config::default(),
+        [
+            ChannelConfig::single_ended(p.P0_02),
+            ChannelConfig::single_ended(p.P0_04),
+        ],
+    );
+
+    let sensors = AdcSensors {
+        r_pull_up: 10000,
+        r_pull_down: 4790,
+        vin_mv: Cell::new(0),
+        temp_mv: Cell::new(0),
+    };
+    saadc.calibrate().await;
+    // LightControl checks the battery right away
+    sensors.measure(&mut saadc).await;
+
+    let storage = RecordStore::new(NrfFlash {
+        nvmc: RefCell::new(Nvmc::new(p.NVMC)),
+        first_page: STORAGE_FIRST_PAGE,
+        pages: STORAGE_PAGES,
+    });
+
+    let light_control = LightControl::new(
+        PullUpButton {
+            pin: Input::new(p.P0_03.degrade(), Pull::Up),
+        },
+        PullUpButton {
+            pin: Input::new(p.P0_28.degrade(), Pull::Up),
+        },
+        PullUpButton {
+            pin: Input::new(p.P0_29.degrade(), Pull::Up),
+        },
+        &led_low,
+        &led_high,
+        &rgb,
+        &edt,
+        &sensors,
+        &storage,
+        COMMUTE,
+    );
+
+    light_control.start();
+    light_control.jump_start();
+
+    let serial = UsbSerial::create();
+    let reset = || {
+        reset_to_bootloader();
+    };
+    let console = Console::new(&serial, &reset);
+
+    join5(
+        run(&light_control, &edt, pending::<Action>),
+        sensors.measure_every(&mut saadc, SENSORS_PERIOD),
+        async {
+            loop {
+                watchdog.pet();
+                Timer::after(WATCHDOG_PERIOD).await;
+            }
+        },
+        async {
+            let mut buf = [0; MAX_PACKET_SIZE];
+            loop {
+                receiver.wait_connection().await;
+                while let Ok(n) = receiver.read_packet(&mut buf).await {
+                    console.receive(&buf[..n], &light_control);
+                }
+                // nobody is listening anymore
+                light_control.stop_telemetry();
+            }
+        },
+        async {
+            loop {
+                sender.wait_connection().await;
+                while serial.flush(&mut sender).await.is_ok() {
+                    Timer::after(SERIAL_FLUSH_PERIOD).await;
+                }
+            }
+        },
+    )
+    .await;
+}
+
+fn enable_external_osc() {
+    let clock: pac::CLOCK = unsafe { mem::transmute(()) };
+    clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+    while clock.events_hfclkstarted.read().bits() != 1 {}
+}
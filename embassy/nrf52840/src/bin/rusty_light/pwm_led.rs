@@ -0,0 +1,43 @@
+use core::cell::Cell;
+
+use embassy_nrf::pwm::{Instance, SimplePwm};
+
+use light_control::bsp::led::Led;
+use light_control::perceived_light_math::fill_pwm_duty_cycle_values;
+
+/// One channel of a [SimplePwm], several LEDs can share the same PWM peripheral
+pub struct PwmLed<'a, 'd, T: Instance> {
+    duties: [u16; 101],
+    pwm: &'a SimplePwm<'d, T>,
+    channel: usize,
+    state: Cell<u32>,
+}
+
+impl<'a, 'd, T: Instance> PwmLed<'a, 'd, T> {
+    pub fn create(pwm: &'a SimplePwm<'d, T>, channel: usize) -> Self {
+        let mut led = PwmLed {
+            duties: [0; 101],
+            pwm,
+            channel,
+            state: Cell::new(0),
+        };
+
+        fill_pwm_duty_cycle_values(&mut led.duties, 0, pwm.max_duty());
+        led.set(0);
+        led
+    }
+}
+
+impl<'a, 'd, T: Instance> Led for PwmLed<'a, 'd, T> {
+    fn set(&self, pwm: u32) {
+        self.state.set(pwm);
+        let duty_cycle = self.duties[pwm as usize];
+        // nRF PWM counts the duty cycle as the time the pin is low
+        self.pwm
+            .set_duty(self.channel, self.pwm.max_duty() - duty_cycle);
+    }
+
+    fn get(&self) -> u32 {
+        self.state.get()
+    }
+}
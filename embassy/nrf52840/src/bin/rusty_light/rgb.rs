@@ -0,0 +1,34 @@
+use core::cell::{Cell, RefCell};
+
+use embassy_nrf::gpio::{AnyPin, Output};
+
+use light_control::bsp::rgb::{Rgb, BLUE, GREEN, RED};
+
+/// Common anode RGB LED, a color is on when its pin is low
+pub struct GpioRgb<'d> {
+    pub r: RefCell<Output<'d, AnyPin>>,
+    pub g: RefCell<Output<'d, AnyPin>>,
+    pub b: RefCell<Output<'d, AnyPin>>,
+    pub state: Cell<u8>,
+}
+
+impl<'d> Rgb for GpioRgb<'d> {
+    fn set_rgb(&self, rgb: u8) {
+        self.state.set(rgb);
+        set_color(&self.r, rgb & RED > 0);
+        set_color(&self.g, rgb & GREEN > 0);
+        set_color(&self.b, rgb & BLUE > 0);
+    }
+
+    fn get_rgb(&self) -> u8 {
+        self.state.get()
+    }
+}
+
+fn set_color(pin: &RefCell<Output<'_, AnyPin>>, on: bool) {
+    if on {
+        pin.borrow_mut().set_low()
+    } else {
+        pin.borrow_mut().set_high()
+    }
+}
@@ -0,0 +1,108 @@
+use core::cell::{Cell, RefCell};
+use core::mem;
+
+use embassy_nrf::pac;
+use embassy_nrf::peripherals::USBD;
+use embassy_nrf::usb::vbus_detect::HardwareVbusDetect;
+use embassy_nrf::usb::Driver;
+use embassy_usb::class::cdc_acm;
+use embassy_usb::driver::EndpointError;
+use embassy_usb::{Builder, Config, UsbDevice};
+use static_cell::StaticCell;
+
+use light_control::bsp::serial::Serial;
+
+use crate::Irqs;
+
+pub const MAX_PACKET_SIZE: usize = 64;
+const SERIAL_BUFFER_SIZE: usize = 512;
+
+pub type USBDDriver = Driver<'static, USBD, HardwareVbusDetect>;
+
+pub fn usb_builder(usbd: USBD) -> Builder<'static, USBDDriver> {
+    let driver = Driver::new(usbd, Irqs, HardwareVbusDetect::new(Irqs));
+
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("rusty-light");
+    config.product = Some("rusty-light");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+
+    // Required for windows compatibility.
+    // https://developer.nordicsemi.com/nRF_Connect_SDK/doc/1.9.1/kconfig/CONFIG_CDC_ACM_IAD.html#help
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    static DEVICE_DESC: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONFIG_DESC: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESC: StaticCell<[u8; 256]> = StaticCell::new();
+    static MSOS_DESC: StaticCell<[u8; 128]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 128]> = StaticCell::new();
+
+    Builder::new(
+        driver,
+        config,
+        &mut DEVICE_DESC.init([0; 256])[..],
+        &mut CONFIG_DESC.init([0; 256])[..],
+        &mut BOS_DESC.init([0; 256])[..],
+        &mut MSOS_DESC.init([0; 128])[..],
+        &mut CONTROL_BUF.init([0; 128])[..],
+    )
+}
+
+#[embassy_executor::task]
+pub async fn usb_task(mut device: UsbDevice<'static, USBDDriver>) {
+    device.run().await;
+}
+
+/// Collects the output of the [Console] until [UsbSerial::flush] sends it
+pub struct UsbSerial {
+    buf: RefCell<[u8; SERIAL_BUFFER_SIZE]>,
+    len: Cell<usize>,
+}
+
+impl UsbSerial {
+    pub fn create() -> Self {
+        UsbSerial {
+            buf: RefCell::new([0; SERIAL_BUFFER_SIZE]),
+            len: Cell::new(0),
+        }
+    }
+
+    pub async fn flush(
+        &self,
+        sender: &mut cdc_acm::Sender<'static, USBDDriver>,
+    ) -> Result<(), EndpointError> {
+        // copied, so that nothing is borrowed while waiting for the host
+        let mut out = [0; SERIAL_BUFFER_SIZE];
+        let len = self.len.replace(0);
+        out[..len].copy_from_slice(&self.buf.borrow()[..len]);
+        for packet in out[..len].chunks(MAX_PACKET_SIZE) {
+            sender.write_packet(packet).await?;
+        }
+        if len > 0 && len % MAX_PACKET_SIZE == 0 {
+            // tells the host that the transfer is complete
+            sender.write_packet(&[]).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Serial for UsbSerial {
+    fn write(&self, data: &[u8]) {
+        let len = self.len.get();
+        let n = data.len().min(SERIAL_BUFFER_SIZE - len);
+        self.buf.borrow_mut()[len..len + n].copy_from_slice(&data[..n]);
+        self.len.set(len + n);
+    }
+}
+
+/// Adafruit bootloader stays in DFU mode when GPREGRET is set to this value
+pub fn reset_to_bootloader() -> ! {
+    let power: pac::POWER = unsafe { mem::transmute(()) };
+    power.gpregret.write(|w| unsafe { w.bits(0x57) });
+    cortex_m::peripheral::SCB::sys_reset();
+}
@@ -0,0 +1,162 @@
+use no_std_compat::cell::Cell;
+
+use crate::bsp::adc::SensorError;
+use crate::bsp::ambient::Lux;
+
+/// Daylight, dusk, street-lit city and full night
+pub const DEFAULT_BANDS: &[(u32, usize)] = &[(400, 1), (30, 2), (3, 3), (0, 4)];
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct AutoConfig {
+    /// Min ambient light in lux and the power level from there on, brightest band first.
+    /// The last band should start at 0 lux.
+    pub bands: &'static [(u32, usize)],
+    /// Time constant of the smoothing in ms, streetlights passing by are averaged out.
+    /// Light is smoothed on a log scale, like the eye perceives it, so getting darker is
+    /// as fast as getting brighter.
+    pub smoothing: u32,
+    /// Band boundaries are moved away from the current band by this percentage
+    pub hysteresis: u32,
+    /// Another band has to last for this time in ms until the level is changed, a short
+    /// tunnel does not change it
+    pub hold: u32,
+}
+
+impl Default for AutoConfig {
+    fn default() -> Self {
+        AutoConfig {
+            bands: DEFAULT_BANDS,
+            smoothing: 3000,
+            hysteresis: 30,
+            hold: 3000,
+        }
+    }
+}
+
+impl AutoConfig {
+    pub fn is_valid(&self) -> bool {
+        !self.bands.is_empty()
+            && self.bands.windows(2).all(|it| it[0].0 > it[1].0)
+            && self.bands.iter().all(|&(_, level)| level > 0)
+            && self.hysteresis < 100
+    }
+}
+
+/// Picks the power level from the ambient light
+pub struct AutoBrightness {
+    config: Cell<AutoConfig>,
+    /// Smoothed log2 of the ambient light in mlx with 8 fractional bits, None until the
+    /// first reading
+    filtered: Cell<Option<u32>>,
+    /// Index of the current band in [AutoConfig::bands]
+    band: Cell<Option<usize>>,
+    /// Time in ms another band lasted
+    pending: Cell<u32>,
+}
+
+impl AutoBrightness {
+    pub fn new(config: AutoConfig) -> Self {
+        AutoBrightness {
+            config: Cell::new(config),
+            filtered: Cell::new(None),
+            band: Cell::new(None),
+            pending: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> AutoConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: AutoConfig) {
+        self.config.set(config);
+        self.reset();
+    }
+
+    /// Starts over from the next reading
+    pub fn reset(&self) {
+        self.filtered.set(None);
+        self.band.set(None);
+        self.pending.set(0);
+    }
+
+    /// Smoothed ambient light, None until the first reading
+    pub fn lux(&self) -> Option<Lux> {
+        self.filtered.get().map(|it| Lux((exp2(it) / 1000) as u32))
+    }
+
+    /// Power level for the current band, None until the first reading
+    pub fn level(&self) -> Option<usize> {
+        let bands = self.config.get().bands;
+        self.band.get().and_then(|it| bands.get(it)).map(|it| it.1)
+    }
+
+    /// Smooths [lux] measured [elapsed] ms after the last update and returns the level.
+    /// A failed reading keeps the level.
+    pub fn update(&self, lux: Result<Lux, SensorError>, elapsed: u32) -> Option<usize> {
+        let lux = match lux {
+            Ok(lux) => log2(lux.0 as u64 * 1000),
+            Err(_) => return self.level(),
+        };
+        let config = self.config.get();
+        let filtered = match self.filtered.get() {
+            None => lux,
+            Some(filtered) => {
+                let weight = elapsed as u64;
+                ((filtered as u64 * config.smoothing as u64 + lux as u64 * weight)
+                    / (config.smoothing as u64 + weight).max(1)) as u32
+            }
+        };
+        self.filtered.set(Some(filtered));
+
+        let band = self.band_of(filtered);
+        match self.band.get() {
+            None => self.band.set(Some(band)),
+            Some(current) if current == band => self.pending.set(0),
+            Some(_) => {
+                self.pending.set(self.pending.get().saturating_add(elapsed));
+                if self.pending.get() >= config.hold {
+                    self.band.set(Some(band));
+                    self.pending.set(0);
+                }
+            }
+        }
+        self.level()
+    }
+
+    /// Band of the smoothed light, boundaries of the current band are widened
+    fn band_of(&self, filtered: u32) -> usize {
+        let millilux = exp2(filtered);
+        let config = self.config.get();
+        let current = self.band.get();
+        config
+            .bands
+            .iter()
+            .enumerate()
+            .position(|(i, &(lux, _))| {
+                let threshold = lux as u64 * 1000;
+                let threshold = match current {
+                    Some(current) if i < current => threshold * (100 + config.hysteresis as u64),
+                    Some(current) if i == current => threshold * (100 - config.hysteresis as u64),
+                    _ => threshold * 100,
+                };
+                millilux * 100 >= threshold
+            })
+            .unwrap_or(config.bands.len() - 1)
+    }
+}
+
+/// log2 of [x] + 1 with 8 fractional bits, the mantissa is linearly approximated
+fn log2(x: u64) -> u32 {
+    let x = x + 1;
+    let exponent = 63 - x.leading_zeros();
+    let fraction = ((x << 8) >> exponent) as u32 - 256;
+    (exponent << 8) + fraction
+}
+
+/// Inverse of [log2]
+fn exp2(log: u32) -> u64 {
+    let exponent = log >> 8;
+    let fraction = (log & 0xFF) as u64;
+    (((256 + fraction) << exponent) >> 8).saturating_sub(1)
+}
@@ -0,0 +1,113 @@
+/// Open circuit voltage of a single cell in mV and the capacity in percent at that voltage,
+/// ordered by voltage from full to empty
+type OcvTable = &'static [(u32, u32)];
+
+/// https://learn.adafruit.com/li-ion-and-lipoly-batteries/voltages
+const LI_ION_OCV: OcvTable = &[
+    (4150, 100),
+    (3850, 70),
+    (3730, 45),
+    (3680, 20),
+    (3640, 13),
+    (3500, 8),
+    (3000, 0),
+];
+
+/// LiFePO4 is flat in the middle, the capacity is less certain there
+const LIFEPO4_OCV: OcvTable = &[
+    (3400, 100),
+    (3320, 90),
+    (3300, 70),
+    (3270, 40),
+    (3250, 30),
+    (3200, 20),
+    (3000, 10),
+    (2500, 0),
+];
+
+pub const MAX_CELLS: u8 = 4;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Chemistry {
+    LiIon,
+    LiFePo4,
+}
+
+impl Chemistry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chemistry::LiIon => "li-ion",
+            Chemistry::LiFePo4 => "lifepo4",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Chemistry> {
+        [Chemistry::LiIon, Chemistry::LiFePo4]
+            .iter()
+            .find(|it| it.name() == name)
+            .copied()
+    }
+
+    fn ocv_table(&self) -> OcvTable {
+        match self {
+            Chemistry::LiIon => LI_ION_OCV,
+            Chemistry::LiFePo4 => LIFEPO4_OCV,
+        }
+    }
+}
+
+/// Battery pack of [cells] in series, converts the pack voltage to the capacity
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct BatteryModel {
+    pub chemistry: Chemistry,
+    pub cells: u8,
+}
+
+impl Default for BatteryModel {
+    /// 2S Li-ion
+    fn default() -> Self {
+        BatteryModel {
+            chemistry: Chemistry::LiIon,
+            cells: 2,
+        }
+    }
+}
+
+impl BatteryModel {
+    pub fn is_valid(&self) -> bool {
+        self.cells >= 1 && self.cells <= MAX_CELLS
+    }
+
+    /// Pack voltage in mV at 100 %
+    pub fn full_mv(&self) -> u32 {
+        self.pack_mv(self.chemistry.ocv_table()[0].0)
+    }
+
+    /// Pack voltage in mV at 0 %
+    pub fn empty_mv(&self) -> u32 {
+        let table = self.chemistry.ocv_table();
+        self.pack_mv(table[table.len() - 1].0)
+    }
+
+    /// Capacity in percent, linearly interpolated between the points of the OCV table.
+    /// Voltages outside of the table are clamped to 0 or 100 %.
+    pub fn capacity(&self, battery_voltage_mv: u32) -> u32 {
+        let table = self.chemistry.ocv_table();
+        if battery_voltage_mv >= self.full_mv() {
+            return 100;
+        }
+        table
+            .windows(2)
+            .find(|it| battery_voltage_mv >= self.pack_mv(it[1].0))
+            .map(|it| {
+                let (high_mv, high) = (self.pack_mv(it[0].0), it[0].1);
+                let (low_mv, low) = (self.pack_mv(it[1].0), it[1].1);
+                low + (battery_voltage_mv - low_mv) * (high - low) / (high_mv - low_mv)
+            })
+            .unwrap_or(0)
+    }
+
+    fn pack_mv(&self, cell_mv: u32) -> u32 {
+        cell_mv * self.cells as u32
+    }
+}
@@ -28,8 +28,157 @@ pub mod led {
 }
 
 pub mod adc {
+    /// Voltage in mV
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct Millivolts(pub u32);
+
+    /// Current in mA
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct Milliamps(pub u32);
+
+    /// Temperature in tenths of a degree Celsius
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct Decidegrees(pub i32);
+
+    impl Decidegrees {
+        pub fn from_celsius(celsius: i32) -> Self {
+            Decidegrees(celsius * 10)
+        }
+
+        /// Whole degrees, rounded down
+        pub fn celsius(self) -> i32 {
+            self.0.div_euclid(10)
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum SensorError {
+        /// Conversion failed or timed out
+        Adc,
+        /// Reading is at the supply rail, e.g. the NTC is not connected
+        Disconnected,
+        /// Reading is at the ground rail
+        ShortCircuit,
+    }
+
+    impl SensorError {
+        pub fn name(&self) -> &'static str {
+            match self {
+                SensorError::Adc => "adc",
+                SensorError::Disconnected => "disconnected",
+                SensorError::ShortCircuit => "short circuit",
+            }
+        }
+    }
+
+    /// Measurements of the battery and the LED temperature. Load compensation of the battery
+    /// voltage is up to [LightControl], which knows the LED outputs.
     pub trait Sensors {
-        fn battery_voltage(&self, high_percentage: u32, low_percentage: u32) -> u32;
-        fn temp(&self) -> i32;
+        /// Battery voltage as measured, without any load compensation
+        fn battery_voltage(&self) -> Result<Millivolts, SensorError>;
+        fn temp(&self) -> Result<Decidegrees, SensorError>;
+    }
+}
+
+pub mod motion {
+    use crate::bsp::adc::SensorError;
+
+    /// Acceleration in mg, x points forward, y to the left and z up
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct Acceleration {
+        pub x: i32,
+        pub y: i32,
+        pub z: i32,
+    }
+
+    pub trait Accelerometer {
+        fn acceleration(&self) -> Result<Acceleration, SensorError>;
+    }
+}
+
+pub mod ambient {
+    use crate::bsp::adc::SensorError;
+
+    /// Illuminance in lx
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct Lux(pub u32);
+
+    /// Light sensor facing up or forward, shaded from the own light
+    pub trait AmbientLight {
+        fn lux(&self) -> Result<Lux, SensorError>;
+    }
+}
+
+pub mod brake {
+    /// Brake lever switch, or [DecelerationBrake](crate::rear::DecelerationBrake) on top of
+    /// an accelerometer
+    pub trait BrakeInput {
+        fn is_braking(&self) -> bool;
+    }
+}
+
+pub mod wheel {
+    /// Reed switch or hub dynamo, pulses are counted by an interrupt or a timer
+    pub trait WheelSensor {
+        /// Pulses since start, wraps around
+        fn pulses(&self) -> u32;
+    }
+}
+
+pub mod serial {
+    /// Byte stream to the host, e.g. USB CDC or UART. Bytes which do not fit are dropped.
+    pub trait Serial {
+        fn write(&self, data: &[u8]);
+    }
+}
+
+pub mod storage {
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum StorageError {
+        /// Value is too large or the key is reserved
+        InvalidRecord,
+        /// Not enough space even after compaction
+        Full,
+        /// Flash failed to erase or program
+        Flash,
+    }
+
+    /// Small key-value store for settings which have to survive power cycles
+    pub trait Storage {
+        /// Copies the latest value of [key] into [buf] and returns its length
+        fn read(&self, key: u8, buf: &mut [u8]) -> Option<usize>;
+        fn write(&self, key: u8, value: &[u8]) -> Result<(), StorageError>;
+    }
+
+    /// Erasable pages of non-volatile memory. Erased memory reads as 0xFF.
+    pub trait FlashPages {
+        fn page_size(&self) -> usize;
+        fn page_count(&self) -> usize;
+        fn read(&self, page: usize, offset: usize, buf: &mut [u8]);
+        /// [offset] and length of [data] are multiples of 8, each location is written once after erase
+        fn write(&self, page: usize, offset: usize, data: &[u8]) -> Result<(), StorageError>;
+        fn erase(&self, page: usize) -> Result<(), StorageError>;
+    }
+
+    impl<T: FlashPages> FlashPages for &T {
+        fn page_size(&self) -> usize {
+            (*self).page_size()
+        }
+
+        fn page_count(&self) -> usize {
+            (*self).page_count()
+        }
+
+        fn read(&self, page: usize, offset: usize, buf: &mut [u8]) {
+            (*self).read(page, offset, buf)
+        }
+
+        fn write(&self, page: usize, offset: usize, data: &[u8]) -> Result<(), StorageError> {
+            (*self).write(page, offset, data)
+        }
+
+        fn erase(&self, page: usize) -> Result<(), StorageError> {
+            (*self).erase(page)
+        }
     }
 }
@@ -0,0 +1,585 @@
+use core::fmt::{self, Write};
+use core::ops::RangeInclusive;
+
+use no_std_compat::cell::{Cell, RefCell};
+
+use crate::battery::{BatteryModel, Chemistry, MAX_CELLS};
+use crate::bsp::adc::SensorError;
+use crate::bsp::pin::Pin;
+use crate::bsp::serial::Serial;
+use crate::control::LightControl;
+use crate::gestures::GestureConfig;
+use crate::pattern::{Pattern, StrobeConfig, PATTERNS, STROBE};
+use crate::profile::{Profile, PROFILES};
+use crate::rear::{RearConfig, RearMode};
+
+/// Longer lines are rejected
+pub const LINE_SIZE: usize = 64;
+
+/// Telemetry periods in ms accepted by `telemetry`, besides 0 which stops it
+pub const TELEMETRY_PERIOD: RangeInclusive<u32> = 10..=60000;
+
+/// Commands and their description, as printed by `help`
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("help", "lists commands"),
+    (
+        "status",
+        "prints level, beam, profile, throttle, motion and a sensor fault if any",
+    ),
+    (
+        "battery [<li-ion|lifepo4> <cells>]",
+        "prints measured and compensated voltage in mV and capacity in % or changes the battery pack",
+    ),
+    (
+        "gauge",
+        "prints remaining mAh and %, current in mA and resistance in mOhm",
+    ),
+    (
+        "runtime",
+        "prints minutes left at the current level and keyed by every level, - if unlimited",
+    ),
+    ("temp", "prints temperature in C"),
+    ("outputs", "prints the output in % keyed by the role of every channel"),
+    (
+        "rear [<steady|pulse>]",
+        "prints or changes the rear light mode, prints whether the brake light is on",
+    ),
+    (
+        "auto [<on|off>]",
+        "prints or switches the auto mode, prints ambient lx and the level bias",
+    ),
+    (
+        "speed [<on|off>]",
+        "prints km/h and whether the speed mode is on or switches it",
+    ),
+    (
+        "dipping [<on|off>]",
+        "prints or switches the auto dipping, prints whether the high beam is dipped",
+    ),
+    (
+        "lock [<on|off>]",
+        "prints or changes whether the light is off and the buttons are locked",
+    ),
+    ("level <n>", "sets the power level, leaves the auto and the speed mode"),
+    ("beam <low|high>", "switches the high beam"),
+    ("profile [<name>]", "prints or switches the profile"),
+    (
+        "pattern [<name>|strobe <on ms> <off ms>|off]",
+        "prints, plays or stops a flashing pattern",
+    ),
+    (
+        "gesture [<long_click|multi_click|hold_repeat> <ms>]",
+        "prints or changes gesture timings",
+    ),
+    (
+        "telemetry <ms>",
+        "streams binary telemetry frames every 10 to 60000 ms, 0 stops",
+    ),
+    ("reset", "reboots the light"),
+];
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum GestureTiming {
+    LongClick,
+    MultiClick,
+    HoldRepeat,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Command {
+    Help,
+    Status,
+    Battery(Option<BatteryModel>),
+    Gauge,
+    Runtime,
+    Temp,
+    Outputs,
+    Rear(Option<RearMode>),
+    Auto(Option<bool>),
+    Speed(Option<bool>),
+    Dipping(Option<bool>),
+    Lock(Option<bool>),
+    Level(usize),
+    Beam {
+        high: bool,
+    },
+    Profile(Option<Profile>),
+    Pattern(Option<Pattern>),
+    StopPattern,
+    Gesture(Option<(GestureTiming, u32)>),
+    /// Period in ms, 0 stops the telemetry
+    Telemetry(u32),
+    Reset,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum CommandError {
+    UnknownCommand,
+    MissingArgument,
+    InvalidArgument,
+    TooManyArguments,
+    LineTooLong,
+    /// Command was understood, but the light cannot do it, e.g. the level does not exist
+    Rejected,
+}
+
+impl CommandError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            CommandError::UnknownCommand => "unknown command, try help",
+            CommandError::MissingArgument => "missing argument",
+            CommandError::InvalidArgument => "invalid argument",
+            CommandError::TooManyArguments => "too many arguments",
+            CommandError::LineTooLong => "line too long",
+            CommandError::Rejected => "rejected",
+        }
+    }
+}
+
+/// Parses a line without the line terminator, e.g. `level 3`
+pub fn parse(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_ascii_whitespace();
+    let name = words.next().ok_or(CommandError::UnknownCommand)?;
+    let command = match name {
+        "help" => Command::Help,
+        "status" => Command::Status,
+        "battery" => match words.next() {
+            Some(chemistry) => {
+                let chemistry =
+                    Chemistry::by_name(chemistry).ok_or(CommandError::InvalidArgument)?;
+                let cells = parse_number(words.next())?;
+                if cells == 0 || cells > MAX_CELLS as u32 {
+                    return Err(CommandError::InvalidArgument);
+                }
+                Command::Battery(Some(BatteryModel {
+                    chemistry,
+                    cells: cells as u8,
+                }))
+            }
+            None => Command::Battery(None),
+        },
+        "gauge" => Command::Gauge,
+        "runtime" => Command::Runtime,
+        "temp" => Command::Temp,
+        "outputs" => Command::Outputs,
+        "rear" => match words.next() {
+            Some(mode) => Command::Rear(Some(
+                RearMode::by_name(mode).ok_or(CommandError::InvalidArgument)?,
+            )),
+            None => Command::Rear(None),
+        },
+        "auto" => match words.next() {
+            Some("on") => Command::Auto(Some(true)),
+            Some("off") => Command::Auto(Some(false)),
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => Command::Auto(None),
+        },
+        "speed" => match words.next() {
+            Some("on") => Command::Speed(Some(true)),
+            Some("off") => Command::Speed(Some(false)),
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => Command::Speed(None),
+        },
+        "dipping" => match words.next() {
+            Some("on") => Command::Dipping(Some(true)),
+            Some("off") => Command::Dipping(Some(false)),
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => Command::Dipping(None),
+        },
+        "lock" => match words.next() {
+            Some("on") => Command::Lock(Some(true)),
+            Some("off") => Command::Lock(Some(false)),
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => Command::Lock(None),
+        },
+        "level" => Command::Level(parse_number(words.next())? as usize),
+        "beam" => match words.next() {
+            Some("high") => Command::Beam { high: true },
+            Some("low") => Command::Beam { high: false },
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => return Err(CommandError::MissingArgument),
+        },
+        "profile" => match words.next() {
+            Some(name) => Command::Profile(Some(
+                Profile::by_name(name).ok_or(CommandError::InvalidArgument)?,
+            )),
+            None => Command::Profile(None),
+        },
+        "pattern" => match words.next() {
+            Some("off") => Command::StopPattern,
+            Some("strobe") if words.clone().next().is_some() => {
+                Command::Pattern(Some(Pattern::Strobe(StrobeConfig {
+                    on: parse_number(words.next())?.min(u16::MAX as u32) as u16,
+                    off: parse_number(words.next())?.min(u16::MAX as u32) as u16,
+                    ..STROBE
+                })))
+            }
+            Some(name) => Command::Pattern(Some(
+                Pattern::by_name(name).ok_or(CommandError::InvalidArgument)?,
+            )),
+            None => Command::Pattern(None),
+        },
+        "gesture" => match words.next() {
+            Some(timing) => {
+                let timing = match timing {
+                    "long_click" => GestureTiming::LongClick,
+                    "multi_click" => GestureTiming::MultiClick,
+                    "hold_repeat" => GestureTiming::HoldRepeat,
+                    _ => return Err(CommandError::InvalidArgument),
+                };
+                let ms = parse_number(words.next())?;
+                if ms == 0 {
+                    return Err(CommandError::InvalidArgument);
+                }
+                Command::Gesture(Some((timing, ms)))
+            }
+            None => Command::Gesture(None),
+        },
+        "telemetry" => match parse_number(words.next())? {
+            period if period == 0 || TELEMETRY_PERIOD.contains(&period) => {
+                Command::Telemetry(period)
+            }
+            _ => return Err(CommandError::InvalidArgument),
+        },
+        "reset" => Command::Reset,
+        _ => return Err(CommandError::UnknownCommand),
+    };
+    match words.next() {
+        Some(_) => Err(CommandError::TooManyArguments),
+        None => Ok(command),
+    }
+}
+
+fn parse_number(word: Option<&str>) -> Result<u32, CommandError> {
+    word.ok_or(CommandError::MissingArgument)?
+        .parse()
+        .map_err(|_| CommandError::InvalidArgument)
+}
+
+/// Line-buffered command console on top of a [Serial].
+///
+/// Received bytes are collected until `\r` or `\n`, so commands may be split across
+/// packets. Each command is answered with zero or more tab-separated `key\tvalue` lines
+/// followed by `ok`, or with a single `error\t<message>` line.
+pub struct Console<'a> {
+    serial: &'a dyn Serial,
+    /// Called by `reset`, platform specific
+    reset: &'a dyn Fn(),
+    line: RefCell<[u8; LINE_SIZE]>,
+    len: Cell<usize>,
+    /// Rest of the line is dropped
+    overflow: Cell<bool>,
+}
+
+impl<'a> Console<'a> {
+    pub fn new(serial: &'a dyn Serial, reset: &'a dyn Fn()) -> Self {
+        Console {
+            serial,
+            reset,
+            line: RefCell::new([0; LINE_SIZE]),
+            len: Cell::new(0),
+            overflow: Cell::new(false),
+        }
+    }
+
+    /// Buffers [data] and executes all completed lines
+    pub fn receive<P: Pin, M: Pin, T: Pin>(
+        &self,
+        data: &[u8],
+        light_control: &LightControl<'a, P, M, T>,
+    ) {
+        for &byte in data {
+            match byte {
+                b'\r' | b'\n' => self.end_line(light_control),
+                _ if self.len.get() < LINE_SIZE => {
+                    self.line.borrow_mut()[self.len.get()] = byte;
+                    self.len.set(self.len.get() + 1);
+                }
+                _ => self.overflow.set(true),
+            }
+        }
+    }
+
+    fn end_line<P: Pin, M: Pin, T: Pin>(&self, light_control: &LightControl<'a, P, M, T>) {
+        let len = self.len.replace(0);
+        if self.overflow.replace(false) {
+            self.reply(Err(CommandError::LineTooLong));
+            return;
+        }
+        let line = self.line.borrow();
+        let result = match core::str::from_utf8(&line[..len]) {
+            // \r\n or an empty line
+            Ok(line) if line.trim().is_empty() => return,
+            Ok(line) => parse(line).and_then(|command| self.execute(command, light_control)),
+            Err(_) => Err(CommandError::UnknownCommand),
+        };
+        self.reply(result);
+    }
+
+    fn execute<P: Pin, M: Pin, T: Pin>(
+        &self,
+        command: Command,
+        light_control: &LightControl<'a, P, M, T>,
+    ) -> Result<(), CommandError> {
+        let mut out = Writer(self.serial);
+        // Serial drops what does not fit, formatting itself cannot fail
+        let _ = match command {
+            Command::Help => COMMANDS
+                .iter()
+                .try_for_each(|(usage, description)| writeln!(out, "{}\t{}", usage, description)),
+            Command::Status => writeln!(
+                out,
+                "level\t{}\nbeam\t{}\nprofile\t{}\nthrottle\t{}\nmotion\t{}",
+                light_control.power_level(),
+                if light_control.high_beam() {
+                    "high"
+                } else {
+                    "low"
+                },
+                light_control.profile().name,
+                light_control.throttle(),
+                light_control.motion_state().name(),
+            )
+            .and_then(|_| match light_control.sensor_fault() {
+                Some(fault) => writeln!(out, "fault\t{}", fault.name()),
+                None => Ok(()),
+            }),
+            Command::Battery(Some(model)) => {
+                light_control.set_battery_model(model);
+                Ok(())
+            }
+            Command::Battery(None) => write_reading(
+                &mut out,
+                "voltage",
+                light_control.battery_voltage_raw().map(|it| it.0),
+            )
+            .and_then(|_| {
+                write_reading(
+                    &mut out,
+                    "compensated",
+                    light_control.battery_voltage().map(|it| it.0),
+                )
+            })
+            .and_then(|_| writeln!(out, "capacity\t{}", light_control.battery_capacity())),
+            Command::Gauge => writeln!(
+                out,
+                "remaining\t{}\ncapacity\t{}\ncurrent\t{}\nresistance\t{}",
+                light_control.remaining_mah(),
+                light_control.remaining_capacity(),
+                light_control.battery_current().0,
+                light_control.battery_resistance(),
+            ),
+            Command::Runtime => {
+                let current = light_control.power_level();
+                write_runtime(&mut out, "runtime", light_control.runtime(current)).and_then(|_| {
+                    (1..=light_control.profile().max_level()).try_for_each(|level| {
+                        write_runtime(&mut out, level, light_control.runtime(level))
+                    })
+                })
+            }
+            Command::Temp => write_reading(
+                &mut out,
+                "temp",
+                light_control.temp().map(|it| it.celsius()),
+            ),
+            Command::Outputs => light_control
+                .outputs()
+                .iter()
+                .flatten()
+                .try_for_each(|(role, output)| writeln!(out, "{}\t{}", role.name(), output)),
+            Command::Rear(Some(mode)) => {
+                light_control.set_rear_config(RearConfig {
+                    mode,
+                    ..light_control.rear_config()
+                });
+                Ok(())
+            }
+            Command::Rear(None) => writeln!(
+                out,
+                "mode\t{}\nbraking\t{}",
+                light_control.rear_config().mode.name(),
+                light_control.is_braking() as u8,
+            ),
+            Command::Auto(Some(auto)) => {
+                if !light_control.set_auto(auto) {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Auto(None) => writeln!(
+                out,
+                "auto\t{}",
+                if light_control.auto() { "on" } else { "off" }
+            )
+            .and_then(|_| match light_control.ambient_lux() {
+                Some(lux) => writeln!(out, "lux\t{}", lux.0),
+                None => writeln!(out, "lux\t-"),
+            })
+            .and_then(|_| writeln!(out, "bias\t{}", light_control.auto_bias())),
+            Command::Speed(Some(speed_mode)) => {
+                if !light_control.set_speed_mode(speed_mode) {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Speed(None) => writeln!(
+                out,
+                "speed\t{}\nmode\t{}",
+                light_control.speed(),
+                if light_control.speed_mode() {
+                    "on"
+                } else {
+                    "off"
+                }
+            ),
+            Command::Dipping(Some(auto_dipping)) => {
+                if !light_control.set_auto_dipping(auto_dipping) {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Dipping(None) => writeln!(
+                out,
+                "dipping\t{}\ndipped\t{}",
+                if light_control.auto_dipping() {
+                    "on"
+                } else {
+                    "off"
+                },
+                light_control.is_dipped() as u8,
+            ),
+            Command::Lock(Some(true)) => {
+                if !light_control.lock() {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Lock(Some(false)) => {
+                if !light_control.unlock() {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Lock(None) => writeln!(out, "locked\t{}", light_control.is_locked() as u8),
+            Command::Level(level) => {
+                if !light_control.set_power_level(level) {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::Beam { high } => {
+                light_control.set_high_beam(high);
+                Ok(())
+            }
+            Command::Profile(Some(profile)) => {
+                light_control.set_profile(profile);
+                Ok(())
+            }
+            Command::Profile(None) => {
+                let current = light_control.profile().name;
+                writeln!(out, "profile\t{}", current).and_then(|_| {
+                    PROFILES
+                        .iter()
+                        .try_for_each(|it| writeln!(out, "available\t{}", it.name))
+                })
+            }
+            Command::Pattern(Some(pattern)) => {
+                if !pattern.is_valid() || !light_control.start_pattern(pattern) {
+                    return Err(CommandError::Rejected);
+                }
+                Ok(())
+            }
+            Command::StopPattern => {
+                light_control.stop_pattern();
+                Ok(())
+            }
+            Command::Pattern(None) => {
+                let current = light_control.pattern().map_or("off", |it| it.name());
+                writeln!(out, "pattern\t{}", current).and_then(|_| {
+                    PATTERNS
+                        .iter()
+                        .try_for_each(|it| writeln!(out, "available\t{}", it.name()))
+                })
+            }
+            Command::Gesture(Some((timing, ms))) => {
+                let config = light_control.gesture_config();
+                light_control.set_gesture_config(match timing {
+                    GestureTiming::LongClick => GestureConfig {
+                        long_click_threshold: ms,
+                        ..config
+                    },
+                    GestureTiming::MultiClick => GestureConfig {
+                        multi_click_window: ms,
+                        ..config
+                    },
+                    GestureTiming::HoldRepeat => GestureConfig {
+                        hold_repeat_period: ms,
+                        ..config
+                    },
+                });
+                Ok(())
+            }
+            Command::Gesture(None) => {
+                let config = light_control.gesture_config();
+                writeln!(
+                    out,
+                    "long_click\t{}\nmulti_click\t{}\nhold_repeat\t{}",
+                    config.long_click_threshold,
+                    config.multi_click_window,
+                    config.hold_repeat_period,
+                )
+            }
+            Command::Telemetry(0) => {
+                light_control.stop_telemetry();
+                Ok(())
+            }
+            Command::Telemetry(period) => {
+                light_control.start_telemetry(self.serial, period);
+                Ok(())
+            }
+            Command::Reset => {
+                (self.reset)();
+                Ok(())
+            }
+        };
+        Ok(())
+    }
+
+    fn reply(&self, result: Result<(), CommandError>) {
+        match result {
+            Ok(()) => self.serial.write(b"ok\n"),
+            Err(error) => {
+                let _ = writeln!(Writer(self.serial), "error\t{}", error.message());
+            }
+        }
+    }
+}
+
+/// Value of a sensor or the name of its fault
+fn write_reading(
+    out: &mut Writer,
+    key: &str,
+    reading: Result<impl fmt::Display, SensorError>,
+) -> fmt::Result {
+    match reading {
+        Ok(value) => writeln!(out, "{}\t{}", key, value),
+        Err(error) => writeln!(out, "{}\t{}", key, error.name()),
+    }
+}
+
+/// Runtime of a level, keyed by the level
+fn write_runtime(out: &mut Writer, key: impl fmt::Display, minutes: Option<u32>) -> fmt::Result {
+    match minutes {
+        Some(minutes) => writeln!(out, "{}\t{}", key, minutes),
+        None => writeln!(out, "{}\t-", key),
+    }
+}
+
+struct Writer<'a>(&'a dyn Serial);
+
+impl Write for Writer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
@@ -1,12 +1,31 @@
 use no_std_compat::cell::Cell;
 
-use crate::battery_voltage_to_capacity::battery_voltage_to_capacity;
-use crate::bsp::adc::Sensors;
+use crate::ambient::{AutoBrightness, AutoConfig};
+use crate::battery::BatteryModel;
+use crate::bsp::adc::{Decidegrees, Milliamps, Millivolts, SensorError, Sensors};
+use crate::bsp::ambient::{AmbientLight, Lux};
+use crate::bsp::brake::BrakeInput;
 use crate::bsp::led::Led;
+use crate::bsp::motion::Accelerometer;
 use crate::bsp::pin::Pin;
 use crate::bsp::rgb::{Rgb, BLUE, GREEN, RED};
-use crate::control::ButtonState::{Clicked, LongClicked, Nothing};
-use crate::edt::EDT;
+use crate::bsp::serial::Serial;
+use crate::bsp::storage::Storage;
+use crate::bsp::wheel::WheelSensor;
+use crate::dipping::{DippingConfig, GlareDetector};
+use crate::edt::{TimerHandle, EDT};
+use crate::fuel_gauge::{FuelGauge, FuelGaugeConfig};
+use crate::gestures::{Button, Gesture, GestureConfig, GestureRecognizer, MINUS, PLUS};
+use crate::motion::{MotionConfig, MotionDetector, MotionState};
+use crate::output::{Channel, ChannelConfig, Mode, Role, HIGH_BEAM, LOW_BEAM, MAX_CHANNELS};
+use crate::pattern::{Pattern, PATTERNS};
+use crate::profile::{Profile, PROFILES};
+use crate::protection::{BatteryProtection, ProtectionConfig, ProtectionState};
+use crate::rear::{RearConfig, RearLight};
+use crate::runtime::{runtime_blinks, runtime_minutes};
+use crate::speed::{SpeedConfig, SpeedEstimator};
+use crate::telemetry::Frame;
+use crate::thermal::{ThermalConfig, ThermalRegulator};
 
 #[derive(Clone, Debug, Eq, PartialEq, Copy)]
 pub enum Action {
@@ -19,86 +38,127 @@ pub enum Action {
     SetPwm {
         start: u8,
         end: u8,
-        i: usize,
-        high_beam: bool,
+        i: u8,
+        channel: u8,
+    },
+    PatternStep {
+        step: u8,
+        channel: u8,
     },
     CheckBatteryAndTemperature,
     IndicateBatteryAndTemperature,
+    SaveSettings,
+    SendTelemetry,
 }
 
 pub const BUTTON_CHECK_PERIOD: u32 = 50;
-pub const LONG_CLICK_THRESHOLD: u32 = 1000;
-
-// 3 or 4 modes?
-// currently max is too bright
-// and 3 with high beam is too bright
-// pub const POWER_LEVELS_LOW: &'static [u8] = &[0, 25, 50, 75, 100];
-// pub const POWER_LEVELS_LOW_AUX: &'static [u8] = &[0, 25, 45, 60, 80];
-// pub const POWER_LEVELS_HIGH: &'static [u8] = &[0, 60, 80, 90, 100];
+pub const BATTERY_CHECK_PERIOD: u32 = 500;
+/// Output in percent while a sensor cannot be read
+pub const SENSOR_FAULT_THROTTLE: u32 = 30;
+/// Settings are saved when they did not change for this time to spare the flash
+pub const SAVE_SETTINGS_DELAY: u32 = 5000;
 
-pub const POWER_LEVELS: &'static [u8] = &[0, 15, 40, 65, 85];
-pub const POWER_LEVELS_LOW: &'static [u8] = POWER_LEVELS;
-pub const POWER_LEVELS_LOW_AUX: &'static [u8] = POWER_LEVELS;
-pub const POWER_LEVELS_HIGH: &'static [u8] = POWER_LEVELS;
+/// Storage key of power level and high beam
+pub const KEY_MODE: u8 = 1;
+/// Storage key of the index in [PROFILES]
+pub const KEY_PROFILE: u8 = 2;
 
-pub const POWER_LEVEL_INIT: usize = 3;
+/// Channels of the LEDs passed to [LightControl::new]
+pub const LOW_BEAM_CHANNEL: usize = 0;
+pub const HIGH_BEAM_CHANNEL: usize = 1;
 
-pub const MAX_POWER_LEVEL: usize = POWER_LEVELS_LOW.len() - 1;
 pub const ANIM_DURATION: u32 = 500;
-const ANIM_SIZE: usize = (60 * ANIM_DURATION / 1000) as usize;
+const ANIM_SIZE: u8 = (60 * ANIM_DURATION / 1000) as u8;
 const ANIM_STEP: u32 = ANIM_DURATION / ANIM_SIZE as u32;
 
-enum ButtonState {
-    Nothing,
-    Clicked,
-    LongClicked,
-}
-
-/// Button which remembers how long has it been held down
-struct StatefulButton<P: Pin> {
-    pin: P,
-    hold_time: Cell<u32>,
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct State {
+    power_level: usize,
+    high_beam: bool,
+    /// Output in percent allowed by the battery
+    throttle: u32,
+    /// Output in percent allowed by the temperature, weighted per channel
+    heat: u32,
 }
 
-impl<P: Pin> StatefulButton<P> {
-    fn check_state(&self, elapsed_time: u32) -> ButtonState {
-        let held = self.hold_time.get();
-        let pin_down = self.pin.is_down();
-
-        self.hold_time
-            .set(if pin_down { held + elapsed_time } else { 0 });
-
-        if pin_down && held == LONG_CLICK_THRESHOLD {
-            LongClicked
-        } else if !pin_down && held > 1 && held < LONG_CLICK_THRESHOLD {
-            Clicked
+impl State {
+    fn mode(&self) -> Mode {
+        if self.high_beam {
+            Mode::HighBeam
         } else {
-            Nothing
+            Mode::LowBeam
         }
     }
 }
 
-#[derive(Copy, Clone)]
-struct State {
-    power_level: usize,
-    high_beam: bool,
-    throttle: u32,
-}
-
 /// Control logic evaluates button states and changes the light intensity
 pub struct LightControl<'a, P: Pin, M: Pin, T: Pin> {
-    plus_pin: StatefulButton<P>,
-    minus_pin: StatefulButton<M>,
-    toggle_pin: StatefulButton<T>,
-    led: &'a dyn Led,
-    led_high: &'a dyn Led,
+    plus_pin: P,
+    minus_pin: M,
+    toggle_pin: T,
+    gestures: GestureRecognizer,
+    thermal: ThermalRegulator,
+    battery: Cell<BatteryModel>,
+    fuel_gauge: FuelGauge,
+    protection: BatteryProtection,
+    rear: RearLight,
+    /// Lights the brake light of [Role::Rear] channels
+    brake: Cell<Option<&'a dyn BrakeInput>>,
+    motion: MotionDetector,
+    accelerometer: Cell<Option<&'a dyn Accelerometer>>,
+    /// State to return to when moving again, while dimmed or off by [motion]
+    wake: Cell<Option<State>>,
+    auto: AutoBrightness,
+    ambient_light: Cell<Option<&'a dyn AmbientLight>>,
+    /// Power level follows the ambient light
+    auto_enabled: Cell<bool>,
+    /// Levels added by the rider to the automatic level
+    auto_bias: Cell<i32>,
+    speed: SpeedEstimator,
+    wheel: Cell<Option<&'a dyn WheelSensor>>,
+    /// Power level and high beam follow the speed
+    speed_mode: Cell<bool>,
+    /// Index in [SpeedConfig::levels] of the last speed, None until measured
+    speed_band: Cell<Option<usize>>,
+    /// Speed was above [SpeedConfig::high_beam_above]
+    fast: Cell<bool>,
+    glare: GlareDetector,
+    forward_light: Cell<Option<&'a dyn AmbientLight>>,
+    /// High beam is dropped while oncoming light is detected
+    auto_dipping: Cell<bool>,
+    /// High beam was dropped by the auto dipping and is restored when the road is clear
+    dipped: Cell<bool>,
+    /// Rider switched the high beam on while oncoming light was detected
+    dipping_overridden: Cell<bool>,
+    /// State to return to when unlocked, while the buttons are locked
+    locked: Cell<Option<State>>,
+    /// Played instead of the steady level of [state]
+    pattern: Cell<Option<Pattern>>,
+    patterns: Cell<&'a [Pattern]>,
+    channels: [Cell<Option<Channel<'a>>>; MAX_CHANNELS],
     rgb: &'a dyn Rgb,
     sensors: &'a dyn Sensors,
+    storage: &'a dyn Storage,
     edt: &'a EDT<Action>,
+    profile: Cell<Profile>,
     state: Cell<State>,
+    /// Next step of the animation or the pattern of every channel
+    animations: [Cell<Option<TimerHandle>>; MAX_CHANNELS],
+    /// Delayed part of the startup animation
+    startup: Cell<Option<TimerHandle>>,
+    blink: Cell<Option<TimerHandle>>,
+    save: Cell<Option<TimerHandle>>,
+    /// Receives a [Frame] on every [Action::SendTelemetry]
+    telemetry: Cell<Option<&'a dyn Serial>>,
+    telemetry_timer: Cell<Option<TimerHandle>>,
+    /// Error of the last failed sensor read
+    fault: Cell<Option<SensorError>>,
 }
 
 impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
+    /// Takes the peripherals every board has, the optional ones are set afterwards
+    // Each argument is a distinct part of the board, grouping them would only move the list
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         plus_pin: P,
         minus_pin: M,
@@ -108,80 +168,742 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
         rgb: &'a dyn Rgb,
         edt: &'a EDT<Action>,
         sensors: &'a dyn Sensors,
+        storage: &'a dyn Storage,
+        profile: Profile,
     ) -> Self {
-        return LightControl {
-            plus_pin: StatefulButton {
-                pin: plus_pin,
-                hold_time: Cell::new(0),
-            },
-            minus_pin: StatefulButton {
-                pin: minus_pin,
-                hold_time: Cell::new(0),
-            },
-            toggle_pin: StatefulButton {
-                pin: toggle_pin,
-                hold_time: Cell::new(0),
-            },
-            led,
-            led_high,
+        debug_assert!(profile.is_valid());
+        LightControl {
+            plus_pin,
+            minus_pin,
+            toggle_pin,
+            gestures: GestureRecognizer::new(GestureConfig::default()),
+            thermal: ThermalRegulator::new(ThermalConfig::default()),
+            battery: Cell::new(BatteryModel::default()),
+            fuel_gauge: FuelGauge::new(FuelGaugeConfig::default()),
+            protection: BatteryProtection::new(ProtectionConfig::default()),
+            rear: RearLight::new(RearConfig::default()),
+            brake: Cell::new(None),
+            motion: MotionDetector::new(MotionConfig::default()),
+            accelerometer: Cell::new(None),
+            wake: Cell::new(None),
+            auto: AutoBrightness::new(AutoConfig::default()),
+            ambient_light: Cell::new(None),
+            auto_enabled: Cell::new(false),
+            auto_bias: Cell::new(0),
+            speed: SpeedEstimator::new(SpeedConfig::default()),
+            wheel: Cell::new(None),
+            speed_mode: Cell::new(false),
+            speed_band: Cell::new(None),
+            fast: Cell::new(false),
+            glare: GlareDetector::new(DippingConfig::default()),
+            forward_light: Cell::new(None),
+            auto_dipping: Cell::new(false),
+            dipped: Cell::new(false),
+            dipping_overridden: Cell::new(false),
+            locked: Cell::new(None),
+            pattern: Cell::new(None),
+            patterns: Cell::new(PATTERNS),
+            channels: [
+                Cell::new(Some(Channel {
+                    led,
+                    config: LOW_BEAM,
+                })),
+                Cell::new(Some(Channel {
+                    led: led_high,
+                    config: HIGH_BEAM,
+                })),
+                Cell::new(None),
+                Cell::new(None),
+                Cell::new(None),
+                Cell::new(None),
+            ],
             rgb,
             edt,
             sensors,
+            storage,
+            profile: Cell::new(profile),
             state: Cell::new(State {
                 power_level: 0,
                 high_beam: false,
                 throttle: 100,
+                heat: 100,
             }),
+            animations: Default::default(),
+            startup: Cell::new(None),
+            blink: Cell::new(None),
+            save: Cell::new(None),
+            telemetry: Cell::new(None),
+            telemetry_timer: Cell::new(None),
+            fault: Cell::new(None),
+        }
+    }
+
+    /// Changes timings of clicks and holds
+    pub fn set_gesture_config(&self, config: GestureConfig) {
+        debug_assert!(config.is_valid());
+        self.gestures.set_config(config);
+    }
+
+    pub fn gesture_config(&self) -> GestureConfig {
+        self.gestures.config()
+    }
+
+    /// Changes target and trip temperatures and the regulator gains
+    pub fn set_thermal_config(&self, config: ThermalConfig) {
+        self.thermal.set_config(config);
+    }
+
+    pub fn thermal_config(&self) -> ThermalConfig {
+        self.thermal.config()
+    }
+
+    /// Changes chemistry and cell count of the battery pack
+    pub fn set_battery_model(&self, model: BatteryModel) {
+        debug_assert!(model.is_valid());
+        self.battery.set(model);
+        self.fuel_gauge.reset();
+    }
+
+    pub fn battery_model(&self) -> BatteryModel {
+        self.battery.get()
+    }
+
+    /// Changes the pack capacity and rest detection of the fuel gauge
+    pub fn set_fuel_gauge_config(&self, config: FuelGaugeConfig) {
+        self.fuel_gauge.set_config(config);
+    }
+
+    pub fn fuel_gauge_config(&self) -> FuelGaugeConfig {
+        self.fuel_gauge.config()
+    }
+
+    /// Changes the capacities at which the battery protection steps in
+    pub fn set_protection_config(&self, config: ProtectionConfig) {
+        self.protection.set_config(config);
+    }
+
+    pub fn protection_config(&self) -> ProtectionConfig {
+        self.protection.config()
+    }
+
+    pub fn protection_state(&self) -> ProtectionState {
+        self.protection.state()
+    }
+
+    /// Leaves the latched [ProtectionState::Shutdown] for a short limp home.
+    /// Returns false if the light was not locked out.
+    pub fn override_lockout(&self) -> bool {
+        if !self.protection.override_lockout() {
+            return false;
+        }
+        self.apply_protection(ProtectionState::LowMode, 100);
+        self.blink(RED, 5, 100);
+        true
+    }
+
+    /// Drives another LED, e.g. a rear light. Returns false if all [MAX_CHANNELS] are taken.
+    pub fn add_channel(&self, led: &'a dyn Led, config: ChannelConfig) -> bool {
+        debug_assert!(config.is_valid());
+        let free = match self.channels.iter().position(|it| it.get().is_none()) {
+            Some(i) => i,
+            None => return false,
         };
+        self.channels[free].set(Some(Channel { led, config }));
+        self.animate_channel(free, &self.state.get());
+        true
+    }
+
+    /// Config of the first channel with [role]
+    pub fn channel_config(&self, role: Role) -> Option<ChannelConfig> {
+        self.channel_by_role(role).map(|(_, it)| it.config)
+    }
+
+    /// Changes level tables, thermal weight and current of the first channel with the role
+    /// of [config]. Returns false if there is no such channel.
+    pub fn set_channel_config(&self, config: ChannelConfig) -> bool {
+        debug_assert!(config.is_valid());
+        match self.channel_by_role(config.role) {
+            Some((i, channel)) => {
+                self.channels[i].set(Some(Channel { config, ..channel }));
+                self.animate_channel(i, &self.state.get());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Roles and outputs in percent of all channels in the order they were added, None if a channel is free
+    pub fn outputs(&self) -> [Option<(Role, u32)>; MAX_CHANNELS] {
+        let mut outputs = [None; MAX_CHANNELS];
+        for (output, channel) in outputs.iter_mut().zip(&self.channels) {
+            *output = channel.get().map(|it| (it.config.role, it.led.get()));
+        }
+        outputs
+    }
+
+    /// Changes mode and brake light of [Role::Rear] channels
+    pub fn set_rear_config(&self, config: RearConfig) {
+        debug_assert!(config.is_valid());
+        self.rear.set_config(config);
+    }
+
+    pub fn rear_config(&self) -> RearConfig {
+        self.rear.config()
+    }
+
+    /// Brake switch or accelerometer, polled every [BUTTON_CHECK_PERIOD]
+    pub fn set_brake_input(&self, brake: &'a dyn BrakeInput) {
+        self.brake.set(Some(brake));
+    }
+
+    /// Brake light is on
+    pub fn is_braking(&self) -> bool {
+        self.rear.is_braking()
+    }
+
+    /// Changes when the light is dimmed and switched off while stationary
+    pub fn set_motion_config(&self, config: MotionConfig) {
+        debug_assert!(config.is_valid());
+        self.motion.set_config(config);
+    }
+
+    pub fn motion_config(&self) -> MotionConfig {
+        self.motion.config()
+    }
+
+    /// Enables dimming while stationary, polled every [BUTTON_CHECK_PERIOD]
+    pub fn set_accelerometer(&self, accelerometer: &'a dyn Accelerometer) {
+        self.accelerometer.set(Some(accelerometer));
+    }
+
+    pub fn motion_state(&self) -> MotionState {
+        self.motion.state()
+    }
+
+    /// Changes the bands and the smoothing of the auto mode
+    pub fn set_auto_config(&self, config: AutoConfig) {
+        debug_assert!(config.is_valid());
+        self.auto.set_config(config);
+    }
+
+    pub fn auto_config(&self) -> AutoConfig {
+        self.auto.config()
+    }
+
+    /// Enables the auto mode, polled every [BATTERY_CHECK_PERIOD]
+    pub fn set_ambient_light(&self, ambient_light: &'a dyn AmbientLight) {
+        self.ambient_light.set(Some(ambient_light));
+    }
+
+    pub fn auto(&self) -> bool {
+        self.auto_enabled.get()
+    }
+
+    /// Lets the ambient light pick the power level, plus and minus add a bias to it.
+    /// Returns false if there is no ambient light sensor.
+    pub fn set_auto(&self, auto: bool) -> bool {
+        if auto && self.ambient_light.get().is_none() {
+            return false;
+        }
+        self.auto_enabled.set(auto);
+        self.auto_bias.set(0);
+        if auto {
+            self.speed_mode.set(false);
+            self.auto.reset();
+            self.check_ambient_light();
+        }
+        true
+    }
+
+    /// Levels added by plus and minus to the level of the auto mode
+    pub fn auto_bias(&self) -> i32 {
+        self.auto_bias.get()
+    }
+
+    /// Smoothed ambient light, None until measured
+    pub fn ambient_lux(&self) -> Option<Lux> {
+        self.auto.lux()
+    }
+
+    /// Changes wheel, speed levels and the high beam speed
+    pub fn set_speed_config(&self, config: SpeedConfig) {
+        debug_assert!(config.is_valid());
+        self.speed.set_config(config);
+    }
+
+    pub fn speed_config(&self) -> SpeedConfig {
+        self.speed.config()
+    }
+
+    /// Enables the speed mode, polled every [BUTTON_CHECK_PERIOD]
+    pub fn set_wheel_sensor(&self, wheel: &'a dyn WheelSensor) {
+        self.wheel.set(Some(wheel));
+    }
+
+    /// Speed in km/h, 0 without a wheel sensor
+    pub fn speed(&self) -> u32 {
+        self.speed.speed()
+    }
+
+    pub fn speed_mode(&self) -> bool {
+        self.speed_mode.get()
+    }
+
+    /// Lets the speed pick the power level and switch the high beam, leaves the auto mode.
+    /// Both are only changed when the speed crosses a band, the rider may override them in
+    /// between. Returns false if there is no wheel sensor.
+    pub fn set_speed_mode(&self, speed_mode: bool) -> bool {
+        if speed_mode && self.wheel.get().is_none() {
+            return false;
+        }
+        self.speed_mode.set(speed_mode);
+        self.speed_band.set(None);
+        self.fast.set(false);
+        if speed_mode {
+            self.auto_enabled.set(false);
+        }
+        true
+    }
+
+    /// Changes the detection of oncoming light and the restore delay
+    pub fn set_dipping_config(&self, config: DippingConfig) {
+        debug_assert!(config.is_valid());
+        self.glare.set_config(config);
+    }
+
+    pub fn dipping_config(&self) -> DippingConfig {
+        self.glare.config()
+    }
+
+    /// Forward facing light sensor, enables the auto dipping, polled every
+    /// [BUTTON_CHECK_PERIOD]
+    pub fn set_forward_light(&self, forward_light: &'a dyn AmbientLight) {
+        self.forward_light.set(Some(forward_light));
+    }
+
+    pub fn auto_dipping(&self) -> bool {
+        self.auto_dipping.get()
+    }
+
+    /// Drops the high beam while oncoming headlights are detected and restores it after
+    /// [DippingConfig::restore_after]. Returns false if there is no forward light sensor.
+    pub fn set_auto_dipping(&self, auto_dipping: bool) -> bool {
+        if auto_dipping && self.forward_light.get().is_none() {
+            return false;
+        }
+        self.auto_dipping.set(auto_dipping);
+        self.dipping_overridden.set(false);
+        if !auto_dipping && self.dipped.take() {
+            self.switch_high_beam(true);
+        }
+        true
+    }
+
+    /// High beam is dropped because of oncoming light
+    pub fn is_dipped(&self) -> bool {
+        self.dipped.get()
+    }
+
+    /// Switches the LEDs off and ignores all buttons but the unlock gesture, e.g. in a bag.
+    /// Returns false if the light was locked already.
+    pub fn lock(&self) -> bool {
+        if self.locked.get().is_some() {
+            return false;
+        }
+        self.stop_pattern();
+        let current = self.state.get();
+        let previous = self.wake.take().unwrap_or(current);
+        self.locked.set(Some(State {
+            high_beam: previous.high_beam || self.dipped.take(),
+            ..previous
+        }));
+        self.rgb.set_rgb(self.rgb.get_rgb() & !BLUE);
+        self.change_state(State {
+            power_level: 0,
+            high_beam: false,
+            ..current
+        });
+        self.indicate_locked();
+        true
+    }
+
+    /// Restores the state from before [lock](Self::lock), returns false if it was not locked
+    pub fn unlock(&self) -> bool {
+        let locked = match self.locked.take() {
+            Some(locked) => locked,
+            None => return false,
+        };
+        self.motion.wake();
+        self.restore(locked);
+        self.blink(GREEN, 3, 100);
+        true
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.get().is_some()
+    }
+
+    /// Patterns cycled through by a triple click on plus, [PATTERNS] by default
+    pub fn set_patterns(&self, patterns: &'a [Pattern]) {
+        debug_assert!(patterns.iter().all(|it| it.is_valid()));
+        self.patterns.set(patterns);
+    }
+
+    /// Pattern played instead of the steady level
+    pub fn pattern(&self) -> Option<Pattern> {
+        self.pattern.get()
+    }
+
+    /// Plays [pattern] until it ends, a button is clicked, the bike is parked, the battery gets
+    /// low or [stop_pattern](Self::stop_pattern) is called. The steady level still changes
+    /// underneath. Returns false in a profile which does not allow flashing lights, like StVZO,
+    /// in the battery low mode or while locked.
+    pub fn start_pattern(&self, pattern: Pattern) -> bool {
+        debug_assert!(pattern.is_valid());
+        if !self.profile.get().allows_flashing || self.is_protected() || self.is_locked() {
+            return false;
+        }
+        self.wake_up();
+        self.cancel(&self.startup);
+        self.pattern.set(Some(pattern));
+        for i in 0..MAX_CHANNELS {
+            self.play_step(i, 0);
+        }
+        true
+    }
+
+    /// Fades back to the steady level, returns false if no pattern was played
+    pub fn stop_pattern(&self) -> bool {
+        if self.pattern.take().is_none() {
+            return false;
+        }
+        let state = self.state.get();
+        for i in 0..MAX_CHANNELS {
+            self.animate_channel(i, &state);
+        }
+        true
+    }
+
+    pub fn power_level(&self) -> usize {
+        self.state.get().power_level
+    }
+
+    /// Returns false if [level] is off, not in the current profile, the battery is too low or
+    /// the light is locked. Leaves the auto and the speed mode.
+    pub fn set_power_level(&self, level: usize) -> bool {
+        if level == 0
+            || level > self.profile.get().max_level()
+            || self.is_protected()
+            || self.is_locked()
+        {
+            return false;
+        }
+        self.wake_up();
+        self.auto_enabled.set(false);
+        self.speed_mode.set(false);
+        let current = self.state.get();
+        if level != current.power_level {
+            self.change_state(State {
+                power_level: level,
+                ..current
+            });
+        }
+        true
+    }
+
+    pub fn high_beam(&self) -> bool {
+        self.state.get().high_beam
+    }
+
+    /// The high beam is not switched on while the battery is too low or the light is locked.
+    /// Switching it on overrides the auto dipping until the oncoming light is gone.
+    pub fn set_high_beam(&self, high_beam: bool) {
+        if self.is_locked() {
+            return;
+        }
+        self.wake_up();
+        self.dipped.set(false);
+        self.dipping_overridden
+            .set(high_beam && self.auto_dipping.get() && self.glare.is_detected());
+        self.switch_high_beam(high_beam);
+    }
+
+    fn switch_high_beam(&self, high_beam: bool) {
+        let current = self.state.get();
+        if high_beam == current.high_beam || (high_beam && self.is_protected()) {
+            return;
+        }
+        self.change_state(State {
+            high_beam,
+            ..current
+        });
+        let rgb = self.rgb.get_rgb();
+        if high_beam {
+            self.rgb.set_rgb(rgb | BLUE);
+        } else {
+            self.rgb.set_rgb(rgb & !BLUE);
+        }
+    }
+
+    /// Output in percent, reduced by high temperature or low battery.
+    /// Channels with a lower [ChannelConfig::thermal_weight] are throttled less by heat.
+    pub fn throttle(&self) -> u32 {
+        let state = self.state.get();
+        state.throttle.min(state.heat)
+    }
+
+    /// Battery voltage as measured under the current load
+    pub fn battery_voltage_raw(&self) -> Result<Millivolts, SensorError> {
+        self.sensors.battery_voltage()
+    }
+
+    /// Battery voltage compensated for the current load with the learned internal resistance
+    pub fn battery_voltage(&self) -> Result<Millivolts, SensorError> {
+        self.sensors.battery_voltage().map(|it| self.compensate(it))
+    }
+
+    /// Battery capacity in percent, 0 if the voltage cannot be measured
+    pub fn battery_capacity(&self) -> u32 {
+        self.battery_voltage()
+            .map_or(0, |it| self.battery.get().capacity(it.0))
+    }
+
+    /// Remaining charge in mAh counted by the fuel gauge
+    pub fn remaining_mah(&self) -> u32 {
+        self.fuel_gauge.remaining_mah()
+    }
+
+    /// Remaining charge in percent counted by the fuel gauge
+    pub fn remaining_capacity(&self) -> u32 {
+        self.fuel_gauge.capacity()
+    }
+
+    /// Current drawn by all channels, as estimated by the fuel gauge
+    pub fn battery_current(&self) -> Milliamps {
+        Milliamps(self.fuel_gauge.current_ma())
+    }
+
+    /// Internal resistance of the battery in mΩ, learned by the fuel gauge
+    pub fn battery_resistance(&self) -> u32 {
+        self.fuel_gauge.resistance()
+    }
+
+    /// Forgets the counted charge, e.g. after the battery was swapped
+    pub fn reset_fuel_gauge(&self) {
+        self.fuel_gauge.reset();
+    }
+
+    /// Minutes until the battery is empty at [level] with the current beam and throttle.
+    /// None if the level draws no current or does not exist.
+    pub fn runtime(&self, level: usize) -> Option<u32> {
+        let profile = self.profile.get();
+        if level > profile.max_level() {
+            return None;
+        }
+        let state = State {
+            power_level: level,
+            ..self.state.get()
+        };
+        let voltage = self.sensors.battery_voltage().map_or(0, |it| it.0);
+        let current = self.load_ma(voltage, &|channel| {
+            Self::channel_output(&profile, &state, &channel.config) as u32
+        });
+        runtime_minutes(self.fuel_gauge.remaining_mah(), current)
+    }
+
+    /// Minutes until the battery is empty at the current power level
+    pub fn current_runtime(&self) -> Option<u32> {
+        self.runtime(self.state.get().power_level)
+    }
+
+    /// Blinks once per started [MINUTES_PER_BLINK] of the current runtime
+    pub fn indicate_runtime(&self) {
+        match self.current_runtime() {
+            Some(minutes) => {
+                let blinks = runtime_blinks(minutes) as u8;
+                self.blink(
+                    Self::battery_color(self.battery_capacity()),
+                    blinks * 2 - 1,
+                    300,
+                );
+            }
+            None => self.indicate_nop(),
+        }
+    }
+
+    pub fn temp(&self) -> Result<Decidegrees, SensorError> {
+        self.sensors.temp()
+    }
+
+    /// Error of the last failed sensor read, the output is limited to
+    /// [SENSOR_FAULT_THROTTLE] until the sensor works again
+    pub fn sensor_fault(&self) -> Option<SensorError> {
+        self.fault.get()
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile.get()
+    }
+
+    /// Switches to another profile, keeping the power level if the new profile has it
+    pub fn set_profile(&self, profile: Profile) {
+        debug_assert!(profile.is_valid());
+        if !profile.allows_flashing {
+            self.stop_pattern();
+        }
+        self.profile.set(profile);
+        let current = self.state.get();
+        self.change_state(State {
+            power_level: current.power_level.min(profile.max_level()),
+            ..current
+        });
+    }
+
+    /// Snapshot of outputs and sensors
+    pub fn telemetry(&self) -> Frame {
+        Frame {
+            timestamp: self.edt.now(),
+            low: self.output_of(LOW_BEAM_CHANNEL) as u8,
+            high: self.output_of(HIGH_BEAM_CHANNEL) as u8,
+            throttle: self.throttle() as u8,
+            battery_mv: self
+                .battery_voltage_raw()
+                .map_or(0, |it| it.0.min(u16::MAX as u32) as u16),
+            capacity: self.battery_capacity() as u8,
+            temp: self.temp().map_or(i16::MIN, |it| {
+                it.celsius().clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16
+            }),
+            rgb: self.rgb.get_rgb(),
+            queue_len: self.edt.len() as u8,
+        }
+    }
+
+    /// Writes an encoded [Frame] to [serial] every [period] ms
+    pub fn start_telemetry(&self, serial: &'a dyn Serial, period: u32) {
+        self.stop_telemetry();
+        self.telemetry.set(Some(serial));
+        self.telemetry_timer
+            .set(self.schedule_every(period, Action::SendTelemetry));
+    }
+
+    pub fn stop_telemetry(&self) {
+        self.cancel(&self.telemetry_timer);
+        self.telemetry.set(None);
     }
 
     pub fn start(&self) {
         self.check_buttons();
+        self.schedule_every(BUTTON_CHECK_PERIOD, Action::CheckButtons);
         self.check_battery_and_temperature();
+        self.schedule_every(BATTERY_CHECK_PERIOD, Action::CheckBatteryAndTemperature);
         self.indicate_battery_and_temperature();
     }
 
+    /// Restores the last used mode and plays the startup animation
     pub fn jump_start(&self) {
-        self.state.set(State {
-            power_level: POWER_LEVEL_INIT,
-            high_beam: false,
-            throttle: 100,
-        });
+        let state = self.restore_state();
+        self.state.set(state);
+        let profile = self.profile.get();
+        if state.high_beam {
+            self.rgb.set_rgb(self.rgb.get_rgb() | BLUE);
+        }
 
-        self.edt.schedule(
-            ANIM_STEP,
-            Action::SetPwm {
-                start: 0,
-                end: POWER_LEVELS_HIGH[POWER_LEVEL_INIT],
-                i: 0,
-                high_beam: true,
-            },
-        );
+        // the high beam flashes up while the others fade in
+        for (i, channel) in self.channels.iter().enumerate() {
+            let config = match channel.get() {
+                Some(channel) => channel.config,
+                None => continue,
+            };
+            let end = Self::channel_output(&profile, &state, &config);
+            if config.role == Role::Rear {
+                // follows the front without fading, see update_rear
+                continue;
+            }
+            if i == HIGH_BEAM_CHANNEL {
+                let peak = profile.high[state.power_level];
+                self.animations[i].set(self.schedule(
+                    ANIM_STEP,
+                    Action::SetPwm {
+                        start: 0,
+                        end: peak,
+                        i: 0,
+                        channel: i as u8,
+                    },
+                ));
+                self.startup.set(self.schedule(
+                    ANIM_DURATION * 2,
+                    Action::SetPwm {
+                        start: peak,
+                        end,
+                        i: 0,
+                        channel: i as u8,
+                    },
+                ));
+            } else {
+                self.animations[i].set(self.schedule(
+                    ANIM_DURATION,
+                    Action::SetPwm {
+                        start: 0,
+                        end,
+                        i: 0,
+                        channel: i as u8,
+                    },
+                ));
+            }
+        }
+    }
 
-        self.edt.schedule(
-            ANIM_DURATION * 2,
-            Action::SetPwm {
-                start: POWER_LEVELS_HIGH[POWER_LEVEL_INIT],
-                end: 0,
-                i: 0,
-                high_beam: true,
-            },
-        );
+    /// Reads profile, power level and high beam from the storage, falls back to the init level
+    fn restore_state(&self) -> State {
+        let mut buf = [0u8; 2];
+        if self.storage.read(KEY_PROFILE, &mut buf) == Some(1) {
+            if let Some(&profile) = PROFILES.get(buf[0] as usize) {
+                self.profile.set(profile);
+            }
+        }
 
-        self.edt.schedule(
-            ANIM_DURATION,
-            Action::SetPwm {
-                start: 0,
-                end: POWER_LEVELS_LOW[POWER_LEVEL_INIT],
-                i: 0,
-                high_beam: false,
+        let profile = self.profile.get();
+        let restored = self.storage.read(KEY_MODE, &mut buf) == Some(2)
+            && buf[0] > 0
+            && buf[0] as usize <= profile.max_level();
+        State {
+            power_level: if restored {
+                buf[0] as usize
+            } else {
+                profile.init_level
             },
-        );
+            high_beam: restored && buf[1] != 0,
+            throttle: 100,
+            heat: 100,
+        }
+    }
+
+    /// Errors are ignored, the light works fine without the settings
+    fn save_settings(&self) {
+        let state = self
+            .locked
+            .get()
+            .or(self.wake.get())
+            .unwrap_or(self.state.get());
+        let high_beam = state.high_beam || self.dipped.get();
+        let _ = self
+            .storage
+            .write(KEY_MODE, &[state.power_level as u8, high_beam as u8]);
+        let profile = self.profile.get();
+        if let Some(i) = PROFILES.iter().position(|it| it.name == profile.name) {
+            let _ = self.storage.write(KEY_PROFILE, &[i as u8]);
+        }
     }
 
     pub fn process_message(&self, action: Action) {
         match action {
-            Action::CheckButtons => self.check_buttons(),
+            Action::CheckButtons => {
+                self.check_buttons();
+                self.check_motion();
+                self.check_speed();
+                self.check_glare();
+                self.update_rear();
+            }
             Action::Blink {
                 color,
                 blinks,
@@ -191,12 +913,26 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
                 start,
                 end,
                 i,
-                high_beam,
+                channel,
             } => {
-                self.continue_led_animation(start, end, i, high_beam);
+                self.continue_led_animation(start, end, i, channel as usize);
+            }
+            Action::PatternStep { step, channel } => {
+                self.play_step(channel as usize, step as usize);
+            }
+            Action::CheckBatteryAndTemperature => {
+                self.check_battery_and_temperature();
+                self.check_ambient_light();
             }
-            Action::CheckBatteryAndTemperature => self.check_battery_and_temperature(),
             Action::IndicateBatteryAndTemperature => self.indicate_battery_and_temperature(),
+            Action::SaveSettings => self.save_settings(),
+            Action::SendTelemetry => self.send_telemetry(),
+        }
+    }
+
+    fn send_telemetry(&self) {
+        if let Some(serial) = self.telemetry.get() {
+            serial.write(&self.telemetry().encode());
         }
     }
 
@@ -215,34 +951,138 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
                 blinks: blinks - 1,
                 period,
             };
-            self.edt.schedule(period as u32, action);
+            self.blink.set(self.schedule(period as u32, action));
+        } else {
+            self.blink.set(None);
         }
     }
 
     fn check_buttons(&self) {
-        match self.minus_pin.check_state(BUTTON_CHECK_PERIOD) {
-            Clicked => self.on_minus_clicked(),
-            LongClicked => self.on_long_clicked(),
-            Nothing => {}
+        let pins_down = [
+            self.plus_pin.is_down(),
+            self.minus_pin.is_down(),
+            self.toggle_pin.is_down(),
+        ];
+        self.gestures
+            .update(pins_down, BUTTON_CHECK_PERIOD, &mut |gesture| {
+                self.on_gesture(gesture)
+            });
+    }
+
+    /// Overflows are counted in [EDT::stats], there is nothing else to do about them here
+    fn schedule(&self, delay: u32, action: Action) -> Option<TimerHandle> {
+        let result = self.edt.schedule(delay, action);
+        debug_assert!(result.is_ok(), "EDT overflow: {:?}", action);
+        result.ok()
+    }
+
+    fn schedule_every(&self, period: u32, action: Action) -> Option<TimerHandle> {
+        let result = self.edt.schedule_every(period, action);
+        debug_assert!(result.is_ok(), "EDT overflow: {:?}", action);
+        result.ok()
+    }
+
+    fn cancel(&self, handle: &Cell<Option<TimerHandle>>) {
+        if let Some(handle) = handle.take() {
+            self.edt.cancel(handle);
         }
+    }
 
-        match self.plus_pin.check_state(BUTTON_CHECK_PERIOD) {
-            Clicked => self.on_plus_clicked(),
-            LongClicked => self.on_minus_clicked(),
-            Nothing => {}
+    fn on_gesture(&self, gesture: Gesture) {
+        if self.is_locked() {
+            return self.on_locked_gesture(gesture);
+        }
+        if gesture == Gesture::ClickAndHold(Button::Toggle) {
+            self.lock();
+            return;
         }
+        // gestures other than the next pattern return to the steady level
+        if gesture != Gesture::TripleClick(Button::Plus) && self.stop_pattern() {
+            self.indicate_click();
+            return;
+        }
+        match self.protection.state() {
+            ProtectionState::Shutdown => {
+                // locked out, plus and minus together override it
+                if gesture
+                    == (Gesture::Chord {
+                        buttons: PLUS | MINUS,
+                    })
+                {
+                    self.override_lockout();
+                } else if let Gesture::Click(_) = gesture {
+                    self.blink(RED, 1, 50);
+                }
+                return;
+            }
+            ProtectionState::LowMode => match gesture {
+                Gesture::Click(Button::Plus)
+                | Gesture::DoubleClick(Button::Plus)
+                | Gesture::Click(Button::Toggle) => {
+                    self.indicate_nop();
+                    return;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        // the first press only wakes the light up
+        if self.wake_up() {
+            return;
+        }
+        if self.auto_enabled.get() {
+            match gesture {
+                Gesture::Click(Button::Plus) => return self.change_auto_bias(1),
+                Gesture::Click(Button::Minus) => return self.change_auto_bias(-1),
+                // jumping to a level is manual
+                Gesture::DoubleClick(Button::Plus) | Gesture::DoubleClick(Button::Minus) => {
+                    self.auto_enabled.set(false)
+                }
+                _ => {}
+            }
+        }
+        match gesture {
+            Gesture::Click(Button::Plus) => self.on_plus_clicked(),
+            Gesture::Click(Button::Minus) => self.on_minus_clicked(),
+            Gesture::Click(Button::Toggle) => self.on_toggle_clicked(),
+            Gesture::DoubleClick(Button::Plus) => self.on_plus_double_clicked(),
+            Gesture::DoubleClick(Button::Minus) => self.on_minus_double_clicked(),
+            Gesture::DoubleClick(Button::Toggle) => self.indicate_runtime(),
+            Gesture::TripleClick(Button::Toggle) => self.on_toggle_triple_clicked(),
+            Gesture::TripleClick(Button::Plus) => self.on_plus_triple_clicked(),
+            Gesture::LongClick(Button::Toggle) => self.on_toggle_long_clicked(),
+            Gesture::LongClick(Button::Plus) => self.on_plus_long_clicked(),
+            Gesture::LongClick(Button::Minus) => self.on_minus_long_clicked(),
+            _ => {}
+        }
+    }
 
-        match self.toggle_pin.check_state(BUTTON_CHECK_PERIOD) {
-            Clicked => self.on_toggle_clicked(),
-            LongClicked => {}
-            Nothing => {}
+    /// Only the unlock gesture works while locked, holding a button peeks at the lowest level
+    fn on_locked_gesture(&self, gesture: Gesture) {
+        match gesture {
+            Gesture::ClickAndHold(Button::Toggle) => {
+                self.unlock();
+            }
+            Gesture::LongClick(_) => self.peek(1),
+            Gesture::Released(_) => self.peek(0),
+            Gesture::HoldRepeat(_) => {}
+            _ => self.indicate_locked(),
         }
+    }
 
-        self.edt.schedule(BUTTON_CHECK_PERIOD, Action::CheckButtons);
+    /// Shows [power_level] while locked
+    fn peek(&self, power_level: usize) {
+        let current = self.state.get();
+        if power_level != current.power_level {
+            self.change_state(State {
+                power_level,
+                ..current
+            });
+        }
     }
 
     fn on_plus_clicked(&self) {
-        if self.state.get().power_level < MAX_POWER_LEVEL {
+        if self.state.get().power_level < self.profile.get().max_level() {
             self.increment_power_level();
             self.indicate_click();
         } else {
@@ -259,33 +1099,116 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
         }
     }
 
-    fn on_long_clicked(&self) {
-        self.indicate_nop();
+    /// Jumps to the max power level
+    fn on_plus_double_clicked(&self) {
+        let current = self.state.get();
+        let max_level = self.profile.get().max_level();
+        if current.power_level < max_level {
+            self.change_state(State {
+                power_level: max_level,
+                ..current
+            });
+            self.indicate_click();
+        } else {
+            self.indicate_nop();
+        }
     }
 
-    fn on_toggle_clicked(&self) {
+    /// Jumps to the lowest power level
+    fn on_minus_double_clicked(&self) {
         let current = self.state.get();
-        self.change_state(State {
-            high_beam: !current.high_beam,
-            ..current
+        if current.power_level > 1 {
+            self.change_state(State {
+                power_level: 1,
+                ..current
+            });
+            self.indicate_click();
+        } else {
+            self.indicate_nop();
+        }
+    }
+
+    fn on_toggle_clicked(&self) {
+        self.set_high_beam(!self.state.get().high_beam);
+    }
+
+    /// Cycles through [PROFILES] and blinks the number of the selected one
+    fn on_toggle_triple_clicked(&self) {
+        let current = self.profile.get();
+        let next = PROFILES
+            .iter()
+            .position(|it| it.name == current.name)
+            .map_or(0, |i| (i + 1) % PROFILES.len());
+        self.set_profile(PROFILES[next]);
+        self.blink(GREEN | BLUE, next as u8 * 2 + 1, 200);
+    }
+
+    /// Switches the auto mode, blinks green three times when it is on
+    fn on_toggle_long_clicked(&self) {
+        if !self.set_auto(!self.auto_enabled.get()) {
+            self.indicate_nop();
+        } else if self.auto_enabled.get() {
+            self.blink(GREEN, 5, 100);
+        } else {
+            self.indicate_click();
+        }
+    }
+
+    /// Plays the next pattern, returns to the steady level after the last one
+    fn on_plus_triple_clicked(&self) {
+        let patterns = self.patterns.get();
+        let next = self.pattern.get().map_or(0, |current| {
+            patterns
+                .iter()
+                .position(|it| *it == current)
+                .map_or(0, |i| i + 1)
         });
-        let rgb = self.rgb.get_rgb();
-        if current.high_beam {
-            self.rgb.set_rgb(rgb & !BLUE);
+        match patterns.get(next) {
+            Some(&pattern) if self.start_pattern(pattern) => {}
+            Some(_) => self.indicate_nop(),
+            None => {
+                self.stop_pattern();
+                self.indicate_click();
+            }
+        }
+    }
+
+    /// Switches the speed mode, blinks blue three times when it is on
+    fn on_plus_long_clicked(&self) {
+        if !self.set_speed_mode(!self.speed_mode.get()) {
+            self.indicate_nop();
+        } else if self.speed_mode.get() {
+            self.blink(BLUE, 5, 100);
         } else {
-            self.rgb.set_rgb(rgb | BLUE);
+            self.indicate_click();
         }
     }
 
+    /// Switches the auto dipping, blinks magenta three times when it is on
+    fn on_minus_long_clicked(&self) {
+        if !self.set_auto_dipping(!self.auto_dipping.get()) {
+            self.indicate_nop();
+        } else if self.auto_dipping.get() {
+            self.blink(RED | BLUE, 5, 100);
+        } else {
+            self.indicate_click();
+        }
+    }
+
+    /// Raises or lowers the automatic level by one, as long as it stays in the profile
+    fn change_auto_bias(&self, change: i32) {
+        let level = self.state.get().power_level as i32 + change;
+        if level < 1 || level > self.profile.get().max_level() as i32 {
+            self.indicate_nop();
+            return;
+        }
+        self.auto_bias.set(self.auto_bias.get() + change);
+        self.apply_auto_level();
+        self.indicate_click();
+    }
+
     fn remove_blinks(&self) {
-        self.edt.remove(|action| match action {
-            Action::Blink {
-                color: _,
-                blinks: _,
-                period: _,
-            } => true,
-            _ => false,
-        });
+        self.cancel(&self.blink);
     }
 
     fn blink(&self, color: u8, times: u8, period: u16) {
@@ -293,14 +1216,19 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
         let rgb = rgb | color;
         self.rgb.set_rgb(rgb);
         self.remove_blinks();
-        self.edt.schedule(
+        self.blink.set(self.schedule(
             period as u32,
             Action::Blink {
                 color,
                 blinks: times,
                 period,
             },
-        );
+        ));
+    }
+
+    /// Red and green twice, shown instead of the click
+    fn indicate_locked(&self) {
+        self.blink(RED | GREEN, 3, 100);
     }
 
     fn indicate_nop(&self) {
@@ -311,55 +1239,309 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
         self.blink(Self::battery_color(self.battery_capacity()), 1, 500);
     }
 
-    fn battery_capacity(&self) -> u32 {
-        let battery_voltage_mv = self
-            .sensors
-            .battery_voltage(self.led_high.get(), self.led.get());
-        battery_voltage_to_capacity(battery_voltage_mv)
-    }
-
     fn check_battery_and_temperature(&self) {
+        let voltage = self.sensors.battery_voltage();
         let temp = self.sensors.temp();
-        let battery_capacity = self.battery_capacity();
-        let throttle = calc_throttle(temp, battery_capacity);
+        let fault = temp.err().or(voltage.err());
+        if fault.is_some() && self.fault.get().is_none() {
+            self.indicate_fault();
+        }
+        self.fault.set(fault);
+
+        if let Ok(temp) = temp {
+            // the minus button may share the wire of the NTC, a hold reads as overheating
+            if !self.minus_pin.is_down() {
+                self.thermal.update(temp.celsius());
+            }
+        }
+        match voltage {
+            Ok(voltage) => {
+                // without load compensation, the gauge does it with the learned resistance
+                let current = self.load_ma(voltage.0, &|channel| channel.led.get());
+                self.fuel_gauge
+                    .update(self.edt.now(), &self.battery.get(), voltage.0, current);
+                let capacity = self.battery.get().capacity(self.compensate(voltage).0);
+                let previous = self.protection.state();
+                let protection = self.protection.update(capacity, BATTERY_CHECK_PERIOD);
+                if previous == ProtectionState::Normal && protection == ProtectionState::Warning {
+                    self.indicate_battery_warning();
+                }
+                self.apply_protection(protection, battery_throttle(capacity));
+            }
+            // the protection keeps its state until the voltage can be measured again
+            Err(_) => self.apply_protection(self.protection.state(), 100),
+        }
+    }
+
+    /// Current in mA drawn by all channels at [voltage] with [output] in percent per channel
+    fn load_ma(&self, voltage: u32, output: &dyn Fn(&Channel) -> u32) -> u32 {
+        self.channels
+            .iter()
+            .filter_map(|it| it.get())
+            .map(|it| it.config.current_ma(voltage, output(&it)))
+            .sum()
+    }
+
+    fn compensate(&self, voltage: Millivolts) -> Millivolts {
+        let current = self.load_ma(voltage.0, &|channel| channel.led.get());
+        Millivolts(self.fuel_gauge.open_circuit_voltage(voltage.0, current))
+    }
+
+    /// Output in percent allowed by the temperature, heat cannot be controlled without it
+    fn thermal_throttle(&self) -> u32 {
+        match self.fault.get() {
+            Some(_) => self.thermal.output().min(SENSOR_FAULT_THROTTLE),
+            None => self.thermal.output(),
+        }
+    }
+
+    /// In [ProtectionState::LowMode] the lowest level is kept at full output, only heat
+    /// throttles it
+    fn apply_protection(&self, protection: ProtectionState, battery_throttle: u32) {
+        if protection >= ProtectionState::LowMode {
+            // patterns would bypass the level limit
+            self.stop_pattern();
+        }
         let state = self.state.get();
-        if throttle != state.throttle {
-            self.change_state(State { throttle, ..state });
+        let thermal_throttle = self.thermal_throttle();
+        let new_state = match protection {
+            ProtectionState::Normal | ProtectionState::Warning => State {
+                throttle: battery_throttle,
+                heat: thermal_throttle,
+                ..state
+            },
+            ProtectionState::LowMode => State {
+                power_level: state.power_level.min(1),
+                high_beam: false,
+                throttle: 100,
+                heat: thermal_throttle,
+            },
+            ProtectionState::Shutdown => State {
+                throttle: 0,
+                heat: thermal_throttle,
+                ..state
+            },
+        };
+        if new_state == state {
+            return;
+        }
+        if state.high_beam && !new_state.high_beam {
+            self.rgb.set_rgb(self.rgb.get_rgb() & !BLUE);
+        }
+        self.change_state(new_state);
+    }
+
+    /// Follows the ambient light in the auto mode. Levels are not changed while dimmed by
+    /// [check_motion] or limited by the battery protection.
+    fn check_ambient_light(&self) {
+        let ambient_light = match self.ambient_light.get() {
+            Some(ambient_light) => ambient_light,
+            None => return,
+        };
+        self.auto.update(ambient_light.lux(), BATTERY_CHECK_PERIOD);
+        self.apply_auto_level();
+    }
+
+    fn apply_auto_level(&self) {
+        let level = match self.auto.level() {
+            Some(level) => level,
+            None => return,
+        };
+        if !self.auto_enabled.get() || self.is_paused() || self.is_protected() {
+            return;
+        }
+        let max_level = self.profile.get().max_level() as i32;
+        let level = (level as i32 + self.auto_bias.get()).clamp(1, max_level) as usize;
+        self.set_power_level_automatically(level);
+    }
+
+    /// Follows the speed in the speed mode, on band changes only
+    fn check_speed(&self) {
+        let wheel = match self.wheel.get() {
+            Some(wheel) => wheel,
+            None => return,
+        };
+        self.speed.update(self.edt.now(), wheel.pulses());
+        if !self.speed_mode.get() || self.is_paused() || self.is_protected() {
+            return;
+        }
+        let config = self.speed.config();
+        let speed = self.speed.speed();
+
+        let band = config.band(speed, self.speed_band.get());
+        if self.speed_band.replace(Some(band)) != Some(band) {
+            let max_level = self.profile.get().max_level();
+            self.set_power_level_automatically(config.levels[band].1.min(max_level));
+        }
+        let fast = config.high_beam(speed, self.fast.get());
+        if self.fast.replace(fast) != fast {
+            if fast && self.auto_dipping.get() && self.glare.is_detected() {
+                self.dipped.set(true);
+            } else {
+                self.set_high_beam(fast);
+            }
+        }
+    }
+
+    /// Drops the high beam for oncoming light in the auto dipping, restores it when the
+    /// road is clear
+    fn check_glare(&self) {
+        let forward_light = match self.forward_light.get() {
+            Some(forward_light) => forward_light,
+            None => return,
+        };
+        let detected = self.glare.update(forward_light.lux(), BUTTON_CHECK_PERIOD);
+        if !self.auto_dipping.get() || self.is_paused() {
+            return;
+        }
+        if !detected {
+            self.dipping_overridden.set(false);
+            if self.dipped.take() {
+                self.switch_high_beam(true);
+            }
+        } else if self.state.get().high_beam && !self.dipping_overridden.get() {
+            self.dipped.set(true);
+            self.switch_high_beam(false);
         }
-        self.edt.schedule(500, Action::CheckBatteryAndTemperature);
+    }
+
+    /// Changes the level without leaving the auto or the speed mode
+    fn set_power_level_automatically(&self, level: usize) {
+        let current = self.state.get();
+        if level != current.power_level {
+            self.change_state(State {
+                power_level: level,
+                ..current
+            });
+        }
+    }
+
+    /// Dims or switches the light off while stationary, restores it on movement
+    fn check_motion(&self) {
+        let accelerometer = match self.accelerometer.get() {
+            Some(accelerometer) if !self.is_locked() => accelerometer,
+            _ => return,
+        };
+        let previous = self.motion.state();
+        let motion = self
+            .motion
+            .update(accelerometer.acceleration(), BUTTON_CHECK_PERIOD);
+        if motion == previous {
+            return;
+        }
+        let current = self.state.get();
+        let power_level = match motion {
+            MotionState::Moving => {
+                self.wake_up();
+                return;
+            }
+            MotionState::Parked => current.power_level.min(self.motion.config().parking_level),
+            MotionState::Off => 0,
+        };
+        self.stop_pattern();
+        if self.wake.get().is_none() {
+            self.wake.set(Some(State {
+                high_beam: current.high_beam || self.dipped.take(),
+                ..current
+            }));
+        }
+        if current.high_beam {
+            self.rgb.set_rgb(self.rgb.get_rgb() & !BLUE);
+        }
+        self.change_state(State {
+            power_level,
+            high_beam: false,
+            ..current
+        });
+    }
+
+    /// Restores the state from before [check_motion] dimmed the light.
+    /// Returns false if the light was not dimmed.
+    fn wake_up(&self) -> bool {
+        self.motion.wake();
+        let wake = match self.wake.take() {
+            Some(wake) => wake,
+            None => return false,
+        };
+        self.restore(wake);
+        true
+    }
+
+    /// Returns to the power level and the high beam of [state], as far as the protection allows
+    fn restore(&self, state: State) {
+        let current = self.state.get();
+        let protected = self.is_protected();
+        let new_state = State {
+            power_level: if protected {
+                state.power_level.min(1)
+            } else {
+                state.power_level
+            },
+            high_beam: state.high_beam && !protected,
+            ..current
+        };
+        if new_state.high_beam {
+            self.rgb.set_rgb(self.rgb.get_rgb() | BLUE);
+        }
+        self.change_state(new_state);
+    }
+
+    /// Parked or locked, automatic changes must not switch the light on
+    fn is_paused(&self) -> bool {
+        self.wake.get().is_some() || self.is_locked()
+    }
+
+    /// Output cannot be raised by the rider
+    fn is_protected(&self) -> bool {
+        self.protection.state() >= ProtectionState::LowMode
     }
 
     fn indicate_battery_and_temperature(&self) {
-        let temp = self.sensors.temp();
-        if temp > 60 {
+        let overheated = self
+            .sensors
+            .temp()
+            .is_ok_and(|it| it.celsius() > self.thermal.config().target);
+        if self.fault.get().is_some() {
+            self.indicate_fault();
+            self.schedule(3000, Action::IndicateBatteryAndTemperature);
+        } else if self.protection.state() == ProtectionState::Shutdown {
+            // short to spare the battery
+            self.blink(RED, 1, 50);
+            self.schedule(5000, Action::IndicateBatteryAndTemperature);
+        } else if overheated {
             self.blink(RED | GREEN | BLUE, 13, 50);
-            self.edt
-                .schedule(3000, Action::IndicateBatteryAndTemperature);
+            self.schedule(3000, Action::IndicateBatteryAndTemperature);
         } else {
             let capacity = self.battery_capacity();
             if capacity > 40 {
                 self.blink(Self::battery_color(capacity), 1, 500);
-                self.edt
-                    .schedule(10000, Action::IndicateBatteryAndTemperature);
+                self.schedule(10000, Action::IndicateBatteryAndTemperature);
             } else if capacity > 10 {
                 self.blink(Self::battery_color(capacity), 1, 500);
                 // 10 seconds at 40%, 5 seconds at 20%
-                self.edt
-                    .schedule(capacity * 250, Action::IndicateBatteryAndTemperature);
+                self.schedule(capacity * 250, Action::IndicateBatteryAndTemperature);
             } else if capacity >= 5 {
                 self.blink(RED, 3, 100);
                 // 60 BPS at 13%
-                self.edt
-                    .schedule(capacity * 130, Action::IndicateBatteryAndTemperature);
+                self.schedule(capacity * 130, Action::IndicateBatteryAndTemperature);
             } else {
                 // ~90 BPS
                 self.blink(RED, 3, 100);
-                self.edt
-                    .schedule(660, Action::IndicateBatteryAndTemperature);
+                self.schedule(660, Action::IndicateBatteryAndTemperature);
             };
         };
     }
 
+    /// Red three times when the battery gets low, the level is not limited yet
+    fn indicate_battery_warning(&self) {
+        self.blink(RED, 5, 200);
+    }
+
+    /// Purple, unlike any battery or temperature indication
+    fn indicate_fault(&self) {
+        self.blink(RED | BLUE, 9, 100);
+    }
+
     fn battery_color(battery_capacity: u32) -> u8 {
         if battery_capacity <= 20 {
             RED
@@ -372,7 +1554,7 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
 
     fn increment_power_level(&self) {
         let current = self.state.get();
-        if current.power_level < MAX_POWER_LEVEL {
+        if current.power_level < self.profile.get().max_level() {
             self.change_state(State {
                 power_level: current.power_level + 1,
                 ..current
@@ -391,32 +1573,126 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
     }
 
     fn change_state(&self, new_state: State) {
-        self.edt.remove(|msg| match msg {
-            Action::SetPwm {
-                start: _,
-                end: _,
-                i: _,
-                high_beam: _,
-            } => true,
-            _ => false,
-        });
-        let (low, high) = pwms(&new_state);
-        self.animate_low_beam(low);
-        self.animate_high_beam(high);
+        self.cancel(&self.startup);
+        // a pattern picks the new throttle up with its next step
+        if self.pattern.get().is_none() {
+            for i in 0..MAX_CHANNELS {
+                self.animate_channel(i, &new_state);
+            }
+        }
         self.state.set(new_state);
+
+        // debounce
+        let rescheduled = self
+            .save
+            .get()
+            .is_some_and(|it| self.edt.reschedule(it, SAVE_SETTINGS_DELAY));
+        if !rescheduled {
+            self.save
+                .set(self.schedule(SAVE_SETTINGS_DELAY, Action::SaveSettings));
+        }
     }
 
-    fn animate_high_beam(&self, end: u8) {
-        self.continue_led_animation(self.led_high.get() as u8, end, 0, true);
+    fn channel_by_role(&self, role: Role) -> Option<(usize, Channel<'a>)> {
+        self.channels.iter().enumerate().find_map(|(i, it)| {
+            it.get()
+                .filter(|it| it.config.role == role)
+                .map(|it| (i, it))
+        })
     }
 
-    fn animate_low_beam(&self, end: u8) {
-        self.continue_led_animation(self.led.get() as u8, end, 0, false);
+    /// Output in percent of the channel [i], 0 if it is not used
+    fn output_of(&self, i: usize) -> u32 {
+        self.channels[i].get().map_or(0, |it| it.led.get())
+    }
+
+    fn channel_output(profile: &Profile, state: &State, config: &ChannelConfig) -> u8 {
+        config.output(
+            profile,
+            state.mode(),
+            state.power_level,
+            state.throttle,
+            state.heat,
+        )
+    }
+
+    /// Fades the channel [i] to its output in [state]
+    fn animate_channel(&self, i: usize, state: &State) {
+        if let Some(channel) = self.channels[i]
+            .get()
+            .filter(|it| it.config.role != Role::Rear)
+        {
+            let end = Self::channel_output(&self.profile.get(), state, &channel.config);
+            self.continue_led_animation(channel.led.get() as u8, end, 0, i);
+        }
+    }
+
+    /// Sets the output of [step] of the pattern on the channel [i] and schedules the next one
+    fn play_step(&self, i: usize, step: usize) {
+        let (pattern, channel) = match (self.pattern.get(), self.channels[i].get()) {
+            (Some(pattern), Some(channel)) if channel.config.role != Role::Rear => {
+                (pattern, channel)
+            }
+            _ => return,
+        };
+        let animation = &self.animations[i];
+        self.cancel(animation);
+        let role = channel.config.role;
+        let output = match pattern.step(role, step) {
+            Some(output) => output,
+            None if pattern.len(role) == 0 => {
+                channel.led.set(0);
+                return;
+            }
+            None => {
+                self.stop_pattern();
+                return;
+            }
+        };
+        let state = self.state.get();
+        let config = channel.config;
+        channel
+            .led
+            .set(config.throttled(output.output, state.throttle, state.heat) as u32);
+        let next = if pattern.looping() {
+            (step + 1) % pattern.len(role)
+        } else {
+            step + 1
+        };
+        let action = Action::PatternStep {
+            step: next as u8,
+            channel: i as u8,
+        };
+        animation.set(self.schedule(output.duration as u32, action));
+    }
+
+    /// Sets the output of [Role::Rear] channels, brake light and flashes must not be faded
+    fn update_rear(&self) {
+        let state = self.state.get();
+        let profile = self.profile.get();
+        let braking = self.brake.get().is_some_and(|it| it.is_braking());
+        // flashes may be forbidden by the profile and waste the battery in the low mode
+        let pulse_allowed = profile.allows_flashing && !self.is_protected();
+        let on = state.power_level > 0 && state.throttle > 0;
+        self.rear.update(BUTTON_CHECK_PERIOD, braking, on);
+        for channel in self.channels.iter().filter_map(|it| it.get()) {
+            if channel.config.role == Role::Rear {
+                let base = Self::channel_output(&profile, &state, &channel.config) as u32;
+                channel
+                    .led
+                    .set(self.rear.output(base, state.throttle, pulse_allowed));
+            }
+        }
     }
 
     /// Calculates the pwm level for the given i, sets it and schedules the next step
-    fn continue_led_animation(&self, start: u8, end: u8, i: usize, high_beam: bool) {
-        let led = if high_beam { self.led_high } else { self.led };
+    fn continue_led_animation(&self, start: u8, end: u8, i: u8, channel: usize) {
+        let animation = &self.animations[channel];
+        self.cancel(animation);
+        let led = match self.channels[channel].get() {
+            Some(it) => it.led,
+            None => return,
+        };
         let diff = end as i32 - start as i32;
         let next_value = start as i32 + (diff * (i as i32) / ANIM_SIZE as i32);
         debug_assert!(next_value >= 0);
@@ -427,55 +1703,18 @@ impl<'a, P: Pin, M: Pin, T: Pin> LightControl<'a, P, M, T> {
                 start,
                 end,
                 i: i + 1,
-                high_beam,
+                channel: channel as u8,
             };
-            self.edt.schedule(ANIM_STEP, action);
+            animation.set(self.schedule(ANIM_STEP, action));
         }
     }
 }
 
-fn calc_throttle(temperature: i32, battery_capacity: u32) -> u32 {
-    let temp_throttle = if temperature > 80 {
-        1
-    } else if temperature > 60 {
-        // 100 +(60 - temperature) * 4
-        (340 - temperature * 4) as u32
-    } else {
-        100
-    };
-
-    let bat_throttle = if battery_capacity <= 20 {
+/// Output in percent, reduced below 20% capacity to get home
+fn battery_throttle(battery_capacity: u32) -> u32 {
+    if battery_capacity <= 20 {
         battery_capacity * 5
     } else {
         100
-    };
-
-    if bat_throttle < temp_throttle {
-        bat_throttle
-    } else {
-        temp_throttle
     }
 }
-
-fn pwms(state: &State) -> (u8, u8) {
-    let level = POWER_LEVELS[state.power_level] as u32;
-    if state.high_beam {
-        (
-            (level * state.throttle / 140) as u8,
-            (level * state.throttle / 100) as u8,
-        )
-    } else {
-        ((level * state.throttle / 100) as u8, 0)
-    }
-    // if state.high_beam {
-    //     (
-    //         ((POWER_LEVELS_LOW_AUX[state.power_level] as u32) * state.throttle / 100) as u8,
-    //         ((POWER_LEVELS_HIGH[state.power_level] as u32) * state.throttle / 100) as u8,
-    //     )
-    // } else {
-    //     (
-    //         ((POWER_LEVELS_LOW[state.power_level] as u32) * state.throttle / 100) as u8,
-    //         0,
-    //     )
-    // }
-}
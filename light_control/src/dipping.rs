@@ -0,0 +1,106 @@
+use no_std_compat::cell::Cell;
+
+use crate::bsp::adc::SensorError;
+use crate::bsp::ambient::Lux;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct DippingConfig {
+    /// Rise of the forward light over the background in percent, which counts as oncoming
+    /// headlights
+    pub rise: u32,
+    /// Min forward light in lux for oncoming headlights, noise of a dark road is ignored
+    pub min_lux: u32,
+    /// Time constant in ms the background follows the forward light with, like the reflection
+    /// of the own beam or dusk. It is kept while oncoming light is detected.
+    pub background: u32,
+    /// Time in ms without oncoming light until the high beam is restored
+    pub restore_after: u32,
+}
+
+impl Default for DippingConfig {
+    fn default() -> Self {
+        DippingConfig {
+            rise: 100,
+            min_lux: 3,
+            background: 5000,
+            restore_after: 2000,
+        }
+    }
+}
+
+impl DippingConfig {
+    pub fn is_valid(&self) -> bool {
+        self.rise > 0 && self.min_lux > 0
+    }
+}
+
+/// Detects oncoming headlights as a rise of the forward light over its slowly following
+/// background
+pub struct GlareDetector {
+    config: Cell<DippingConfig>,
+    /// Background light in mlx, None until the first reading
+    background: Cell<Option<u32>>,
+    /// Time in ms since oncoming light was seen
+    dark_for: Cell<u32>,
+}
+
+impl GlareDetector {
+    pub fn new(config: DippingConfig) -> Self {
+        GlareDetector {
+            config: Cell::new(config),
+            background: Cell::new(None),
+            dark_for: Cell::new(u32::MAX),
+        }
+    }
+
+    pub fn config(&self) -> DippingConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: DippingConfig) {
+        self.config.set(config);
+        self.background.set(None);
+        self.dark_for.set(u32::MAX);
+    }
+
+    /// Oncoming light was seen less than [DippingConfig::restore_after] ago
+    pub fn is_detected(&self) -> bool {
+        self.dark_for.get() < self.config.get().restore_after
+    }
+
+    /// Background light, None until the first reading
+    pub fn background(&self) -> Option<Lux> {
+        self.background.get().map(|it| Lux(it / 1000))
+    }
+
+    /// Compares [lux] measured [elapsed] ms after the last update with the background.
+    /// A failed reading counts as no oncoming light, the rider can still dip manually.
+    pub fn update(&self, lux: Result<Lux, SensorError>, elapsed: u32) -> bool {
+        let config = self.config.get();
+        let millilux = match lux {
+            Ok(lux) => lux.0.saturating_mul(1000),
+            Err(_) => {
+                self.dark_for
+                    .set(self.dark_for.get().saturating_add(elapsed));
+                return self.is_detected();
+            }
+        };
+        let background = self.background.get().unwrap_or(millilux);
+        let oncoming = millilux >= config.min_lux.saturating_mul(1000)
+            && millilux as u64 * 100 >= background as u64 * (100 + config.rise as u64);
+        if oncoming {
+            self.dark_for.set(0);
+        } else {
+            self.dark_for
+                .set(self.dark_for.get().saturating_add(elapsed));
+        }
+        if !self.is_detected() {
+            let weight = elapsed as u64;
+            self.background.set(Some(
+                ((background as u64 * config.background as u64 + millilux as u64 * weight)
+                    / (config.background as u64 + weight).max(1)) as u32,
+            ));
+        }
+        self.is_detected()
+    }
+}
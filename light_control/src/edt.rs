@@ -1,25 +1,180 @@
 use no_std_compat::cell::{Cell, RefCell};
-use no_std_compat::cmp::Ordering::Equal;
 
-const SIZE: usize = 10;
+/// Default number of messages which can be scheduled at the same time
+pub const CAPACITY: usize = 16;
+
+/// Timestamps are compared wrap safe, so they must not be further apart than this (24 days)
+pub const MAX_DELAY: u32 = i32::MAX as u32;
+
+/// Wrap safe comparison of timestamps in milliseconds. Time wraps every 49 days.
+pub fn is_before(lhs: u32, rhs: u32) -> bool {
+    (lhs.wrapping_sub(rhs) as i32) < 0
+}
 
 #[derive(Clone, Debug, Eq, PartialEq, Copy)]
 pub struct Msg<T: Sized> {
     pub when: u32,
+    /// Sequence number, messages with the same [when] are handled in the order of scheduling.
+    /// Also identifies the message for [TimerHandle].
     pub order: u32,
+    /// Periodic messages are scheduled again after being handled, 0 for one-shot messages
+    pub period: u32,
     pub payload: T,
 }
 
-pub struct EDT<T> {
+impl<T> Msg<T> {
+    fn is_before(&self, other: &Msg<T>) -> bool {
+        if self.when != other.when {
+            is_before(self.when, other.when)
+        } else {
+            is_before(self.order, other.order)
+        }
+    }
+}
+
+/// Identifies a scheduled message, can be used to cancel or reschedule it
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct TimerHandle {
+    order: u32,
+}
+
+/// Returned by [EDT::schedule] when all slots are taken
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct QueueFull;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy, Default)]
+pub struct Stats {
+    pub len: usize,
+    pub capacity: usize,
+    /// Max number of messages which were in the queue at the same time
+    pub high_water_mark: usize,
+    /// Number of messages which were rejected, because the queue was full
+    pub overflows: u32,
+}
+
+/// Binary min-heap of messages, ordered by [Msg::is_before]
+struct Heap<T, const N: usize> {
+    slots: [Option<Msg<T>>; N],
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> Heap<T, N> {
+    fn get(&self, i: usize) -> Msg<T> {
+        self.slots[i].unwrap()
+    }
+
+    fn push(&mut self, msg: Msg<T>) -> Result<(), QueueFull> {
+        if self.len == N {
+            return Err(QueueFull);
+        }
+        self.slots[self.len] = Some(msg);
+        self.len += 1;
+        self.sift_up(self.len - 1);
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<Msg<T>> {
+        self.slots[0]
+    }
+
+    fn pop(&mut self) -> Option<Msg<T>> {
+        let head = self.slots[0]?;
+        self.len -= 1;
+        self.slots.swap(0, self.len);
+        self.slots[self.len] = None;
+        self.sift_down(0);
+        Some(head)
+    }
+
+    fn position(&self, order: u32) -> Option<usize> {
+        (0..self.len).find(|&i| self.get(i).order == order)
+    }
+
+    /// Removes the message at [i] and restores the heap
+    fn remove_at(&mut self, i: usize) -> Msg<T> {
+        let msg = self.get(i);
+        self.len -= 1;
+        self.slots.swap(i, self.len);
+        self.slots[self.len] = None;
+        if i < self.len {
+            self.sift_down(i);
+            self.sift_up(i);
+        }
+        msg
+    }
+
+    fn retain<F: FnMut(&Msg<T>) -> bool>(&mut self, mut keep: F) {
+        let mut len = 0;
+        for i in 0..self.len {
+            let msg = self.get(i);
+            if keep(&msg) {
+                self.slots[len] = Some(msg);
+                len += 1;
+            }
+        }
+        for slot in &mut self.slots[len..self.len] {
+            *slot = None;
+        }
+        self.len = len;
+        for i in (0..len / 2).rev() {
+            self.sift_down(i);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !self.get(i).is_before(&self.get(parent)) {
+                break;
+            }
+            self.slots.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut first = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.len && self.get(child).is_before(&self.get(first)) {
+                    first = child;
+                }
+            }
+            if first == i {
+                break;
+            }
+            self.slots.swap(i, first);
+            i = first;
+        }
+    }
+}
+
+/// Event dispatch thread. Messages are scheduled with a delay and handed out by [EDT::poll]
+/// when they are due. Holds up to [N] messages without heap allocations.
+pub struct EDT<T, const N: usize = CAPACITY> {
     now: Cell<u32>,
-    pub queue: RefCell<[Option<Msg<T>>; SIZE]>,
+    next_order: Cell<u32>,
+    queue: RefCell<Heap<T, N>>,
+    high_water_mark: Cell<usize>,
+    overflows: Cell<u32>,
 }
 
-impl<T: Copy> EDT<T> {
-    pub fn create() -> EDT<T> {
+impl<T: Copy, const N: usize> EDT<T, N> {
+    pub fn create() -> EDT<T, N> {
+        Self::create_at(0)
+    }
+
+    /// Creates an EDT which starts counting from [now]
+    pub fn create_at(now: u32) -> EDT<T, N> {
         EDT {
-            now: Cell::new(0),
-            queue: RefCell::new([None; SIZE]),
+            now: Cell::new(now),
+            next_order: Cell::new(0),
+            queue: RefCell::new(Heap {
+                slots: [None; N],
+                len: 0,
+            }),
+            high_water_mark: Cell::new(0),
+            overflows: Cell::new(0),
         }
     }
 }
@@ -30,47 +185,56 @@ pub enum Event<T> {
     Halt,
 }
 
-impl<T: Copy> EDT<T> {
+impl<T: Copy, const N: usize> EDT<T, N> {
     pub fn now(&self) -> u32 {
         self.now.get()
     }
 
+    /// Hands out the next due message or tells how long to wait for it. Time is advanced
+    /// to the due time of the next message right away, so the caller has to wait.
     pub fn poll(&self) -> Event<T> {
-        if self.queue.borrow().iter().all(|msg| msg.is_none()) {
-            return Event::Halt;
+        let event = self.poll_at(self.now.get());
+        if let Event::Wait { ms } = event {
+            // processed all messages due before target_time
+            self.now.set(self.now.get().wrapping_add(ms));
+        }
+        event
+    }
+
+    /// Like [poll], but for an external clock, which may be interrupted while waiting.
+    /// Time is set to [now] and is not advanced by [Event::Wait].
+    pub fn poll_at(&self, now: u32) -> Event<T> {
+        if is_before(self.now.get(), now) {
+            self.now.set(now);
         }
 
-        let head = self.peek_head();
-        let to_wait = head.when - self.now.get();
+        let head = match self.queue.borrow().peek() {
+            Some(head) => head,
+            None => return Event::Halt,
+        };
 
-        return if to_wait > 0 {
-            // processed all messages due before target_time
-            self.now.set(head.when);
-            Event::Wait { ms: to_wait }
+        if is_before(self.now.get(), head.when) {
+            Event::Wait {
+                ms: head.when.wrapping_sub(self.now.get()),
+            }
         } else {
-            let next_when = head.when;
-
-            // WTF is that
-            self.queue
-                .borrow_mut()
-                .iter_mut()
-                .filter(|it| {
-                    it.is_some() && it.unwrap().when == head.when && it.unwrap().order == head.order
-                })
-                .take(1)
-                .for_each(|opt| {
-                    *opt = None;
+            let mut queue = self.queue.borrow_mut();
+            queue.pop();
+            if head.period > 0 {
+                // the slot has just been freed, cannot fail
+                let _ = queue.push(Msg {
+                    when: head.when.wrapping_add(head.period),
+                    ..head
                 });
-
-            self.now.set(next_when);
+            }
             Event::Execute { msg: head.payload }
-        };
+        }
     }
 
     /// Advances the time by the given value and feeds messages to the handler
     #[cfg(not(target_arch = "thumbv6m-none-eabi"))]
     pub fn advance_time_by(&self, time: u32, handler: &dyn Fn(T)) {
-        let target = self.now.get() + time;
+        let target = self.now.get().wrapping_add(time);
         let mut elapsed: u32 = 0;
         loop {
             match self.poll() {
@@ -91,46 +255,77 @@ impl<T: Copy> EDT<T> {
         }
     }
 
-    fn peek_head(&self) -> Msg<T> {
-        self.queue
-            .borrow()
-            .iter()
-            .filter_map(|&opt| opt)
-            .min_by(|lhs, rhs| {
-                let by_when = lhs.when.cmp(&rhs.when);
-                if by_when != Equal {
-                    by_when
-                } else {
-                    lhs.order.cmp(&rhs.order)
-                }
-            })
-            .unwrap()
+    /// Schedules the [payload] to be handled after [delay]. Messages with the same due time
+    /// are handled in the order they were scheduled.
+    pub fn schedule(&self, delay: u32, payload: T) -> Result<TimerHandle, QueueFull> {
+        self.push(delay, 0, payload)
     }
 
-    pub fn schedule(&self, delay: u32, payload: T) {
-        let when = self.now.get() + delay;
+    /// Schedules the [payload] to be handled every [period], starting after one [period]
+    pub fn schedule_every(&self, period: u32, payload: T) -> Result<TimerHandle, QueueFull> {
+        debug_assert!(period > 0);
+        self.push(period, period, payload)
+    }
 
-        let order = self
-            .queue
-            .borrow()
-            .iter()
-            .filter(|opt| opt.is_some() && opt.unwrap().when == when)
-            .map(|it| it.unwrap().order)
-            .max()
-            .unwrap_or(0);
+    fn push(&self, delay: u32, period: u32, payload: T) -> Result<TimerHandle, QueueFull> {
+        debug_assert!(delay <= MAX_DELAY);
+        let order = self.next_order.get();
+        let msg = Msg {
+            when: self.now.get().wrapping_add(delay),
+            order,
+            period,
+            payload,
+        };
 
-        self.queue
-            .borrow_mut()
-            .iter_mut()
-            .filter(|it| it.is_none())
-            .take(1)
-            .for_each(|opt| {
-                *opt = Some(Msg {
-                    when,
-                    order,
-                    payload,
+        let mut queue = self.queue.borrow_mut();
+        match queue.push(msg) {
+            Ok(()) => {
+                self.next_order.set(order.wrapping_add(1));
+                if queue.len > self.high_water_mark.get() {
+                    self.high_water_mark.set(queue.len);
+                }
+                Ok(TimerHandle { order })
+            }
+            Err(e) => {
+                self.overflows.set(self.overflows.get() + 1);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns false if the message was already handled or cancelled
+    pub fn cancel(&self, handle: TimerHandle) -> bool {
+        let mut queue = self.queue.borrow_mut();
+        match queue.position(handle.order) {
+            Some(i) => {
+                queue.remove_at(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the message to be due after [delay]. Periodic messages keep their period.
+    /// Returns false if the message was already handled or cancelled.
+    pub fn reschedule(&self, handle: TimerHandle, delay: u32) -> bool {
+        debug_assert!(delay <= MAX_DELAY);
+        let mut queue = self.queue.borrow_mut();
+        match queue.position(handle.order) {
+            Some(i) => {
+                let msg = queue.remove_at(i);
+                // the slot has just been freed, cannot fail
+                let _ = queue.push(Msg {
+                    when: self.now.get().wrapping_add(delay),
+                    ..msg
                 });
-            })
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_scheduled(&self, handle: TimerHandle) -> bool {
+        self.queue.borrow().position(handle.order).is_some()
     }
 
     pub fn remove<F>(&self, mut predicate: F)
@@ -139,16 +334,27 @@ impl<T: Copy> EDT<T> {
     {
         self.queue
             .borrow_mut()
-            .iter_mut()
-            .filter(|it| it.is_some() && predicate(&it.unwrap().payload))
-            .for_each(|opt| {
-                // return to pool
-                *opt = None;
-            });
+            .retain(|msg| !predicate(&msg.payload));
     }
 
-    #[cfg(not(target_arch = "thumbv6m-none-eabi"))]
-    pub fn queue_len(&self) -> usize {
-        self.queue.borrow().len()
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            len: self.len(),
+            capacity: N,
+            high_water_mark: self.high_water_mark.get(),
+            overflows: self.overflows.get(),
+        }
     }
 }
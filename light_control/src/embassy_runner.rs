@@ -0,0 +1,48 @@
+use core::future::Future;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::bsp::pin::Pin;
+use crate::control::{Action, LightControl};
+use crate::edt::{Event, EDT};
+
+/// Drives [LightControl] from an async task instead of the superloop.
+///
+/// Messages of the [EDT] are handled when they are due according to the embassy clock.
+/// [input] is called to get a future for the next external [Action] (e.g. `|| receiver.receive()`
+/// of a channel), which is handled as soon as it completes. The future is dropped when a timer
+/// fires first, so it has to be cancel safe.
+///
+/// [LightControl::start] and [LightControl::jump_start] are expected to be called before.
+pub async fn run<'a, P, M, T, I, F>(
+    light_control: &LightControl<'a, P, M, T>,
+    edt: &EDT<Action>,
+    mut input: I,
+) -> !
+where
+    P: Pin,
+    M: Pin,
+    T: Pin,
+    I: FnMut() -> F,
+    F: Future<Output = Action>,
+{
+    let start = Instant::now();
+    loop {
+        // truncation is fine, EDT time wraps safely
+        let now = start.elapsed().as_millis() as u32;
+        match edt.poll_at(now) {
+            Event::Execute { msg } => light_control.process_message(msg),
+            Event::Wait { ms } => {
+                let timer = Timer::after(Duration::from_millis(ms as u64));
+                if let Either::Second(action) = select(timer, input()).await {
+                    light_control.process_message(action);
+                }
+            }
+            Event::Halt => {
+                let action = input().await;
+                light_control.process_message(action);
+            }
+        }
+    }
+}
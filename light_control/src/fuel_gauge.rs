@@ -0,0 +1,177 @@
+use no_std_compat::cell::Cell;
+
+use crate::battery::BatteryModel;
+
+const MA_MS_PER_MAH: u64 = 3_600_000;
+/// Learned internal resistance is kept within these bounds in mΩ, a bad sample must not
+/// make the voltage-based estimate useless
+const MIN_RESISTANCE: u32 = 20;
+const MAX_RESISTANCE: u32 = 2000;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct FuelGaugeConfig {
+    /// Capacity of the battery pack in mAh
+    pub capacity_mah: u32,
+    /// Battery is considered rested below this current in mA
+    pub rest_current_ma: u32,
+    /// After resting for this time in ms, the voltage is close enough to the open circuit
+    /// voltage to recalibrate the charge
+    pub rest_time: u32,
+    /// Internal resistance in mΩ to start with, until it is learned from load changes
+    pub initial_resistance: u32,
+    /// Min change of the current in mA to learn the internal resistance from
+    pub min_current_step: u32,
+}
+
+impl Default for FuelGaugeConfig {
+    /// Two 3400 mAh 18650 cells in series
+    fn default() -> Self {
+        FuelGaugeConfig {
+            capacity_mah: 3400,
+            rest_current_ma: 10,
+            rest_time: 60_000,
+            initial_resistance: 320,
+            min_current_step: 200,
+        }
+    }
+}
+
+/// Estimates the remaining charge by integrating the current drawn by the LEDs.
+///
+/// The charge starts from the voltage-based capacity of [BatteryModel], the voltage under
+/// load is compensated with the learned internal resistance. Counting drifts, so the charge
+/// is recalibrated whenever the battery rested for [FuelGaugeConfig::rest_time].
+pub struct FuelGauge {
+    config: Cell<FuelGaugeConfig>,
+    /// Remaining charge in mA·ms, None until the first update
+    charge: Cell<Option<u64>>,
+    /// Internal resistance in mΩ
+    resistance: Cell<u32>,
+    /// Time, measured voltage in mV and current in mA of the last update
+    last: Cell<Option<(u32, u32, u32)>>,
+    rested_for: Cell<u32>,
+    /// Charge is recalibrated once per rest
+    calibrated: Cell<bool>,
+}
+
+impl FuelGauge {
+    pub fn new(config: FuelGaugeConfig) -> Self {
+        FuelGauge {
+            config: Cell::new(config),
+            charge: Cell::new(None),
+            resistance: Cell::new(config.initial_resistance),
+            last: Cell::new(None),
+            rested_for: Cell::new(0),
+            calibrated: Cell::new(false),
+        }
+    }
+
+    pub fn config(&self) -> FuelGaugeConfig {
+        self.config.get()
+    }
+
+    /// Changes the config and starts over from the voltage
+    pub fn set_config(&self, config: FuelGaugeConfig) {
+        self.config.set(config);
+        self.resistance.set(config.initial_resistance);
+        self.reset();
+    }
+
+    /// Forgets the counted charge, e.g. after the battery was swapped
+    pub fn reset(&self) {
+        self.charge.set(None);
+        self.last.set(None);
+        self.rested_for.set(0);
+        self.calibrated.set(false);
+    }
+
+    /// Remaining charge in mAh, 0 before the first update
+    pub fn remaining_mah(&self) -> u32 {
+        (self.charge.get().unwrap_or(0) / MA_MS_PER_MAH) as u32
+    }
+
+    /// Remaining charge in percent of [FuelGaugeConfig::capacity_mah]
+    pub fn capacity(&self) -> u32 {
+        let full = self.full_charge();
+        if full == 0 {
+            return 0;
+        }
+        (self.charge.get().unwrap_or(0) * 100 / full) as u32
+    }
+
+    /// Learned internal resistance of the pack in mΩ
+    pub fn resistance(&self) -> u32 {
+        self.resistance.get()
+    }
+
+    /// Current of the last update in mA
+    pub fn current_ma(&self) -> u32 {
+        self.last.get().map_or(0, |(_, _, current)| current)
+    }
+
+    /// Voltage in mV without load, estimated from the measured [voltage] and [current]
+    pub fn open_circuit_voltage(&self, voltage: u32, current: u32) -> u32 {
+        voltage + current * self.resistance.get() / 1000
+    }
+
+    /// Integrates the current since the last update.
+    /// [voltage] is the measured battery voltage in mV without any load compensation,
+    /// [current] is drawn by the LEDs in mA.
+    pub fn update(&self, now: u32, model: &BatteryModel, voltage: u32, current: u32) {
+        if voltage == 0 {
+            // no battery or not measured yet
+            return;
+        }
+        let config = self.config.get();
+
+        match self.last.replace(Some((now, voltage, current))) {
+            None => self.calibrate(model, voltage, current),
+            Some((last_time, last_voltage, last_current)) => {
+                let elapsed = now.wrapping_sub(last_time);
+                // the current is assumed to be constant since the last update
+                let used = last_current as u64 * elapsed as u64;
+                self.charge
+                    .set(Some(self.charge.get().unwrap_or(0).saturating_sub(used)));
+                self.learn_resistance(last_voltage, last_current, voltage, current);
+
+                if current <= config.rest_current_ma {
+                    self.rested_for
+                        .set(self.rested_for.get().saturating_add(elapsed));
+                } else {
+                    self.rested_for.set(0);
+                    self.calibrated.set(false);
+                }
+                if self.rested_for.get() >= config.rest_time && !self.calibrated.get() {
+                    self.calibrate(model, voltage, current);
+                    self.calibrated.set(true);
+                }
+            }
+        }
+    }
+
+    fn calibrate(&self, model: &BatteryModel, voltage: u32, current: u32) {
+        let capacity = model.capacity(self.open_circuit_voltage(voltage, current));
+        self.charge
+            .set(Some(self.full_charge() * capacity as u64 / 100));
+    }
+
+    /// Voltage drops by the internal resistance times the change of the current
+    fn learn_resistance(&self, last_voltage: u32, last_current: u32, voltage: u32, current: u32) {
+        let (d_current, d_voltage) = if current > last_current {
+            (current - last_current, last_voltage as i32 - voltage as i32)
+        } else {
+            (last_current - current, voltage as i32 - last_voltage as i32)
+        };
+        if d_current < self.config.get().min_current_step || d_voltage <= 0 {
+            return;
+        }
+        let sample = (d_voltage as u32 * 1000 / d_current).clamp(MIN_RESISTANCE, MAX_RESISTANCE);
+        // low-pass, a single sample is noisy
+        self.resistance
+            .set((self.resistance.get() * 3 + sample) / 4);
+    }
+
+    fn full_charge(&self) -> u64 {
+        self.config.get().capacity_mah as u64 * MA_MS_PER_MAH
+    }
+}
@@ -0,0 +1,216 @@
+use no_std_compat::cell::Cell;
+
+pub const LONG_CLICK_THRESHOLD: u32 = 1000;
+pub const MULTI_CLICK_WINDOW: u32 = 300;
+pub const HOLD_REPEAT_PERIOD: u32 = 500;
+
+pub const PLUS: u8 = 0x01;
+pub const MINUS: u8 = 0x02;
+pub const TOGGLE: u8 = 0x04;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Button {
+    Plus,
+    Minus,
+    Toggle,
+}
+
+impl Button {
+    pub const ALL: [Button; 3] = [Button::Plus, Button::Minus, Button::Toggle];
+
+    pub fn mask(self) -> u8 {
+        match self {
+            Button::Plus => PLUS,
+            Button::Minus => MINUS,
+            Button::Toggle => TOGGLE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Gesture {
+    /// Single click, reported when no other click followed within the multi-click window
+    Click(Button),
+    DoubleClick(Button),
+    /// Reported immediately when the third click is released
+    TripleClick(Button),
+    /// Button was held down for the long click threshold
+    LongClick(Button),
+    /// Button was clicked and then held down for the long click threshold
+    ClickAndHold(Button),
+    /// Repeated while the button is still held after [LongClick] or [ClickAndHold]
+    HoldRepeat(Button),
+    /// Button was released after [LongClick] or [ClickAndHold]
+    Released(Button),
+    /// Several buttons were pressed together. [buttons] is a mask of [PLUS], [MINUS] and [TOGGLE]
+    Chord {
+        buttons: u8,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct GestureConfig {
+    pub long_click_threshold: u32,
+    pub multi_click_window: u32,
+    pub hold_repeat_period: u32,
+}
+
+impl GestureConfig {
+    pub fn is_valid(&self) -> bool {
+        self.long_click_threshold > 0 && self.multi_click_window > 0 && self.hold_repeat_period > 0
+    }
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        GestureConfig {
+            long_click_threshold: LONG_CLICK_THRESHOLD,
+            multi_click_window: MULTI_CLICK_WINDOW,
+            hold_repeat_period: HOLD_REPEAT_PERIOD,
+        }
+    }
+}
+
+/// State of a single button between two checks
+#[derive(Clone, Copy, Default)]
+struct Tracker {
+    down: bool,
+    held: u32,
+    since_release: u32,
+    since_repeat: u32,
+    clicks: u8,
+    holding: bool,
+}
+
+/// Turns periodically sampled pin states into [Gesture]s
+pub struct GestureRecognizer {
+    config: Cell<GestureConfig>,
+    trackers: [Cell<Tracker>; 3],
+    chord: Cell<u8>,
+}
+
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> Self {
+        GestureRecognizer {
+            config: Cell::new(config),
+            trackers: [
+                Cell::new(Tracker::default()),
+                Cell::new(Tracker::default()),
+                Cell::new(Tracker::default()),
+            ],
+            chord: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> GestureConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: GestureConfig) {
+        self.config.set(config);
+    }
+
+    /// Evaluates pin states (indexed like [Button::ALL]) sampled [elapsed_time] ms after the previous call.
+    /// Recognized gestures are passed to [on_gesture].
+    pub fn update(
+        &self,
+        pins_down: [bool; 3],
+        elapsed_time: u32,
+        on_gesture: &mut dyn FnMut(Gesture),
+    ) {
+        let down_mask = Button::ALL
+            .iter()
+            .zip(pins_down.iter())
+            .filter(|(_, &down)| down)
+            .fold(0, |mask, (button, _)| mask | button.mask());
+
+        let chord = self.chord.get();
+        if chord == 0 && down_mask.count_ones() >= 2 {
+            self.chord.set(down_mask);
+            on_gesture(Gesture::Chord { buttons: down_mask });
+        } else if chord != 0 {
+            // buttons of a chord are ignored until all of them are released
+            self.chord
+                .set(if down_mask == 0 { 0 } else { chord | down_mask });
+        }
+
+        for (i, &button) in Button::ALL.iter().enumerate() {
+            if self.chord.get() & button.mask() != 0 {
+                self.trackers[i].set(Tracker::default());
+            } else {
+                self.update_button(
+                    button,
+                    &self.trackers[i],
+                    pins_down[i],
+                    elapsed_time,
+                    on_gesture,
+                );
+            }
+        }
+    }
+
+    fn update_button(
+        &self,
+        button: Button,
+        tracker: &Cell<Tracker>,
+        pin_down: bool,
+        elapsed_time: u32,
+        on_gesture: &mut dyn FnMut(Gesture),
+    ) {
+        let config = self.config.get();
+        let mut t = tracker.get();
+
+        if pin_down {
+            if !t.down {
+                t.down = true;
+                t.held = 0;
+            }
+            t.held = t.held.saturating_add(elapsed_time);
+
+            if !t.holding {
+                if t.held >= config.long_click_threshold {
+                    t.holding = true;
+                    t.since_repeat = 0;
+                    on_gesture(if t.clicks == 0 {
+                        Gesture::LongClick(button)
+                    } else {
+                        Gesture::ClickAndHold(button)
+                    });
+                    t.clicks = 0;
+                }
+            } else {
+                t.since_repeat += elapsed_time;
+                if t.since_repeat >= config.hold_repeat_period {
+                    t.since_repeat -= config.hold_repeat_period;
+                    on_gesture(Gesture::HoldRepeat(button));
+                }
+            }
+        } else if t.down {
+            t.down = false;
+            if t.holding {
+                t.holding = false;
+                on_gesture(Gesture::Released(button));
+            } else {
+                t.clicks += 1;
+                t.since_release = elapsed_time;
+                // nothing to wait for, there are no gestures with more clicks
+                if t.clicks == 3 {
+                    t.clicks = 0;
+                    on_gesture(Gesture::TripleClick(button));
+                }
+            }
+        } else if t.clicks > 0 {
+            t.since_release += elapsed_time;
+            if t.since_release >= config.multi_click_window {
+                on_gesture(if t.clicks == 1 {
+                    Gesture::Click(button)
+                } else {
+                    Gesture::DoubleClick(button)
+                });
+                t.clicks = 0;
+            }
+        }
+
+        tracker.set(t);
+    }
+}
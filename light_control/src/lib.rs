@@ -1,7 +1,26 @@
 #![no_std]
-pub mod battery_voltage_to_capacity;
+pub mod ambient;
+pub mod battery;
 pub mod bsp;
+pub mod console;
 pub mod control;
+pub mod dipping;
 pub mod edt;
+#[cfg(feature = "embassy")]
+pub mod embassy_runner;
+pub mod fuel_gauge;
+pub mod gestures;
+pub mod motion;
+pub mod ntc;
+pub mod output;
+pub mod pattern;
 pub mod perceived_light_math;
+pub mod profile;
+pub mod protection;
+pub mod rear;
+pub mod record_store;
+pub mod runtime;
+pub mod speed;
+pub mod telemetry;
+pub mod thermal;
 pub mod voltage_to_temp;
@@ -0,0 +1,131 @@
+use no_std_compat::cell::Cell;
+
+use crate::bsp::adc::SensorError;
+use crate::bsp::motion::Acceleration;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy, Ord, PartialOrd)]
+pub enum MotionState {
+    Moving,
+    /// Stationary for [MotionConfig::dim_after], dimmed to [MotionConfig::parking_level]
+    Parked,
+    /// Stationary for [MotionConfig::off_after]
+    Off,
+}
+
+impl MotionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MotionState::Moving => "moving",
+            MotionState::Parked => "parked",
+            MotionState::Off => "off",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct MotionConfig {
+    /// Change of the acceleration in mg, summed over all axes, which counts as movement
+    pub threshold: u32,
+    /// Stationary time in ms until the light is dimmed
+    pub dim_after: u32,
+    /// Stationary time in ms until the light is switched off
+    pub off_after: u32,
+    /// Power level while parked, lower levels are kept
+    pub parking_level: usize,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        MotionConfig {
+            threshold: 60,
+            dim_after: 30_000,
+            off_after: 600_000,
+            parking_level: 1,
+        }
+    }
+}
+
+impl MotionConfig {
+    pub fn is_valid(&self) -> bool {
+        self.dim_after <= self.off_after && self.parking_level > 0
+    }
+}
+
+/// Detects stationary periods by comparing the acceleration with its low-passed history.
+/// Gravity is part of every reading, so the orientation of the light does not matter.
+pub struct MotionDetector {
+    config: Cell<MotionConfig>,
+    /// Low-passed acceleration, None until the first reading
+    reference: Cell<Option<Acceleration>>,
+    /// Time in ms without movement
+    still_for: Cell<u32>,
+}
+
+impl MotionDetector {
+    pub fn new(config: MotionConfig) -> Self {
+        MotionDetector {
+            config: Cell::new(config),
+            reference: Cell::new(None),
+            still_for: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> MotionConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: MotionConfig) {
+        self.config.set(config);
+    }
+
+    pub fn state(&self) -> MotionState {
+        let config = self.config.get();
+        let still_for = self.still_for.get();
+        if still_for >= config.off_after {
+            MotionState::Off
+        } else if still_for >= config.dim_after {
+            MotionState::Parked
+        } else {
+            MotionState::Moving
+        }
+    }
+
+    /// Starts the stationary time over, e.g. when a button was pressed
+    pub fn wake(&self) {
+        self.still_for.set(0);
+    }
+
+    /// Compares [acceleration] with the history, [elapsed] ms after the last update.
+    /// A broken sensor counts as movement, the light must not go dark on the road.
+    pub fn update(
+        &self,
+        acceleration: Result<Acceleration, SensorError>,
+        elapsed: u32,
+    ) -> MotionState {
+        let acceleration = match acceleration {
+            Ok(acceleration) => acceleration,
+            Err(_) => {
+                self.reference.set(None);
+                self.wake();
+                return self.state();
+            }
+        };
+        let reference = self.reference.get().unwrap_or(acceleration);
+        let deviation = acceleration.x.abs_diff(reference.x)
+            + acceleration.y.abs_diff(reference.y)
+            + acceleration.z.abs_diff(reference.z);
+        // low-pass, slow changes like a tilted parked bike do not count
+        self.reference.set(Some(Acceleration {
+            x: (reference.x * 3 + acceleration.x) / 4,
+            y: (reference.y * 3 + acceleration.y) / 4,
+            z: (reference.z * 3 + acceleration.z) / 4,
+        }));
+        if deviation > self.config.get().threshold {
+            self.wake();
+        } else {
+            self.still_for
+                .set(self.still_for.get().saturating_add(elapsed));
+        }
+        self.state()
+    }
+}
@@ -0,0 +1,204 @@
+use crate::bsp::adc::{Decidegrees, Millivolts, SensorError};
+
+/// Fixed-point numbers have 32 fractional bits
+const ONE: i128 = 1 << 32;
+const LN2: i128 = 2_977_044_472;
+/// Reciprocal temperatures are in 10^-12 / K
+const PICO: i128 = 1_000_000_000_000;
+/// 0 °C in hundredths of a Kelvin
+const ZERO_CELSIUS: i128 = 27315;
+
+/// Readings beyond these resistance ratios to the nominal resistance are wiring faults,
+/// roughly -60 °C and 300 °C for common thermistors
+const MAX_RATIO: u32 = 100;
+const MIN_RATIO: u32 = 1000;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum NtcEquation {
+    /// Beta in K, as found in datasheets, and the temperature of the nominal resistance
+    Beta {
+        beta: u32,
+        nominal_temp: Decidegrees,
+    },
+    /// Coefficients of `1/T = A + B ln(R) + C ln(R)^3`, scaled by 10^12
+    SteinhartHart { a: i64, b: i64, c: i64 },
+}
+
+/// Which side of the divider the thermistor is on, the other side is [Ntc::series_resistance]
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum NtcPlacement {
+    /// Between the ADC pin and ground, the voltage drops with heat
+    LowSide,
+    /// Between the reference voltage and the ADC pin, the voltage rises with heat
+    HighSide,
+}
+
+/// Thermistor in a voltage divider, converts the divider voltage to the temperature.
+/// All math is fixed-point and `const`, so tables can be generated at compile time.
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct Ntc {
+    /// Resistance in Ω at [NtcEquation::Beta::nominal_temp], usually at 25 °C
+    pub nominal_resistance: u32,
+    pub equation: NtcEquation,
+    /// Resistor of the divider in Ω
+    pub series_resistance: u32,
+    pub placement: NtcPlacement,
+    /// Voltage across the divider
+    pub reference: Millivolts,
+}
+
+/// 10 kΩ B3435 thermistor with a 10 kΩ pull-up to 3.3 V, the divider
+/// [voltage_to_temp](crate::voltage_to_temp::voltage_to_temp) was fitted to
+pub const NTC_10K_B3435: Ntc = Ntc {
+    nominal_resistance: 10_000,
+    equation: NtcEquation::Beta {
+        beta: 3435,
+        nominal_temp: Decidegrees(250),
+    },
+    series_resistance: 10_000,
+    placement: NtcPlacement::LowSide,
+    reference: Millivolts(3300),
+};
+
+impl Ntc {
+    /// Resistance of the thermistor in Ω at the divider voltage [voltage]
+    pub const fn resistance(&self, voltage: Millivolts) -> Result<u32, SensorError> {
+        let v = voltage.0 as u64;
+        let reference = self.reference.0 as u64;
+        let rs = self.series_resistance as u64;
+        let resistance = match self.placement {
+            NtcPlacement::LowSide => {
+                if v >= reference {
+                    return Err(SensorError::Disconnected);
+                }
+                rs * v / (reference - v)
+            }
+            NtcPlacement::HighSide => {
+                if v == 0 {
+                    return Err(SensorError::Disconnected);
+                }
+                if v >= reference {
+                    return Err(SensorError::ShortCircuit);
+                }
+                rs * (reference - v) / v
+            }
+        };
+        if resistance > self.nominal_resistance as u64 * MAX_RATIO as u64 {
+            Err(SensorError::Disconnected)
+        } else if resistance < (self.nominal_resistance / MIN_RATIO) as u64 || resistance == 0 {
+            Err(SensorError::ShortCircuit)
+        } else {
+            Ok(resistance as u32)
+        }
+    }
+
+    /// Temperature at the divider voltage [voltage], open and shorted thermistors are errors
+    pub const fn temp(&self, voltage: Millivolts) -> Result<Decidegrees, SensorError> {
+        let resistance = match self.resistance(voltage) {
+            Ok(resistance) => resistance as i128,
+            Err(error) => return Err(error),
+        };
+        let inverse = match self.equation {
+            NtcEquation::Beta { beta, nominal_temp } => {
+                let nominal = ZERO_CELSIUS + nominal_temp.0 as i128 * 10;
+                let ratio = ln(resistance * ONE / self.nominal_resistance as i128);
+                PICO * 100 / nominal + ((ratio * PICO / beta as i128) >> 32)
+            }
+            NtcEquation::SteinhartHart { a, b, c } => {
+                let l = ln(resistance * ONE);
+                let l3 = (((l * l) >> 32) * l) >> 32;
+                a as i128 + ((b as i128 * l) >> 32) + ((c as i128 * l3) >> 32)
+            }
+        };
+        if inverse <= 0 {
+            return Err(SensorError::Disconnected);
+        }
+        let centi_kelvin = PICO * 100 / inverse;
+        // rounded to the nearest tenth
+        let centi_celsius = centi_kelvin - ZERO_CELSIUS;
+        Ok(Decidegrees(((centi_celsius + 5).div_euclid(10)) as i32))
+    }
+
+    /// [N] points evenly spread between [from] and [to], for [NtcTable::temp]
+    pub const fn lookup_table<const N: usize>(
+        &self,
+        from: Millivolts,
+        to: Millivolts,
+    ) -> NtcTable<N> {
+        let mut points = [(Millivolts(0), Decidegrees(0)); N];
+        let mut i = 0;
+        while i < N {
+            let span = to.0 as i64 - from.0 as i64;
+            let divisor = if N > 1 { N as i64 - 1 } else { 1 };
+            let voltage = Millivolts((from.0 as i64 + span * i as i64 / divisor) as u32);
+            points[i] = match self.temp(voltage) {
+                Ok(temp) => (voltage, temp),
+                Err(_) => panic!("table exceeds the range of the thermistor"),
+            };
+            i += 1;
+        }
+        NtcTable { points }
+    }
+}
+
+/// Precomputed [Ntc] points, cheaper than the equations on small MCUs
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct NtcTable<const N: usize> {
+    /// Ordered by voltage
+    pub points: [(Millivolts, Decidegrees); N],
+}
+
+impl<const N: usize> NtcTable<N> {
+    /// Linearly interpolated, voltages outside of the table are errors like in [Ntc::temp]
+    pub fn temp(&self, voltage: Millivolts) -> Result<Decidegrees, SensorError> {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(SensorError::Adc),
+        };
+        if voltage < first.0 || voltage > last.0 {
+            // the end at which the temperature rises is the shorted end
+            let hot_end_low = first.1 > last.1;
+            return Err(if (voltage < first.0) == hot_end_low {
+                SensorError::ShortCircuit
+            } else {
+                SensorError::Disconnected
+            });
+        }
+        let i = self
+            .points
+            .windows(2)
+            .position(|it| voltage <= it[1].0)
+            .unwrap_or(0);
+        let (Millivolts(low_mv), Decidegrees(low_temp)) = self.points[i];
+        let (Millivolts(high_mv), Decidegrees(high_temp)) = match self.points.get(i + 1) {
+            Some(&high) => high,
+            None => return Ok(Decidegrees(low_temp)),
+        };
+        if high_mv == low_mv {
+            return Ok(Decidegrees(low_temp));
+        }
+        let offset = (voltage.0 - low_mv) as i32;
+        Ok(Decidegrees(
+            low_temp + (high_temp - low_temp) * offset / (high_mv - low_mv) as i32,
+        ))
+    }
+}
+
+/// Natural logarithm of a positive fixed-point number
+const fn ln(x: i128) -> i128 {
+    // x = 2^k * m, 1 <= m < 2
+    let k = 127 - x.leading_zeros() as i128 - 32;
+    let m = if k >= 0 { x >> k } else { x << -k };
+    // ln(m) = 2 atanh((m - 1) / (m + 1)), the series converges quickly for m < 2
+    let y = ((m - ONE) << 32) / (m + ONE);
+    let y2 = (y * y) >> 32;
+    let mut term = y;
+    let mut sum = 0;
+    let mut n = 1;
+    while term != 0 {
+        sum += term / n;
+        term = (term * y2) >> 32;
+        n += 2;
+    }
+    k * LN2 + 2 * sum
+}
@@ -0,0 +1,177 @@
+use crate::bsp::led::Led;
+use crate::perceived_light_math::current_ma;
+use crate::profile::Profile;
+
+/// Channels of one [LightControl](crate::control::LightControl), including both beams
+pub const MAX_CHANNELS: usize = 6;
+
+/// What a channel is mounted for
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Role {
+    LowBeam,
+    HighBeam,
+    Rear,
+    DaytimeRunning,
+    Trailer,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::LowBeam => "low_beam",
+            Role::HighBeam => "high_beam",
+            Role::Rear => "rear",
+            Role::DaytimeRunning => "daytime_running",
+            Role::Trailer => "trailer",
+        }
+    }
+}
+
+/// Modes of the light, each channel has its own [Levels] in every mode
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Mode {
+    LowBeam,
+    HighBeam,
+}
+
+/// Output of a channel per power level
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Levels {
+    Off,
+    /// [Profile::low]
+    Low,
+    /// [Profile::low_aux] scaled by [Profile::low_aux_ratio]
+    LowAux,
+    /// [Profile::high]
+    High,
+    /// Own table indexed by the power level, the last entry is used for higher levels
+    Table(&'static [u8]),
+    /// Same output in percent at every power level but off
+    Fixed(u8),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct ChannelConfig {
+    pub role: Role,
+    /// Levels in [Mode::LowBeam]
+    pub low_beam: Levels,
+    /// Levels in [Mode::HighBeam]
+    pub high_beam: Levels,
+    /// Share of the thermal throttle in percent, 0 for LEDs that do not heat the head
+    pub thermal_weight: u32,
+    /// Current of the driver at full output in mA
+    pub driver_ma: u32,
+}
+
+pub const LOW_BEAM: ChannelConfig = ChannelConfig {
+    role: Role::LowBeam,
+    low_beam: Levels::Low,
+    high_beam: Levels::LowAux,
+    thermal_weight: 100,
+    driver_ma: 850,
+};
+
+pub const HIGH_BEAM: ChannelConfig = ChannelConfig {
+    role: Role::HighBeam,
+    low_beam: Levels::Off,
+    high_beam: Levels::High,
+    thermal_weight: 100,
+    driver_ma: 1000,
+};
+
+/// Separate housing, dimmer on low power levels to save the battery
+pub const REAR: ChannelConfig = ChannelConfig {
+    role: Role::Rear,
+    low_beam: Levels::Table(&[0, 40, 60, 80, 100]),
+    high_beam: Levels::Table(&[0, 40, 60, 80, 100]),
+    thermal_weight: 0,
+    driver_ma: 60,
+};
+
+/// Shares the heat sink with the beams, off while the high beam is on
+pub const DAYTIME_RUNNING: ChannelConfig = ChannelConfig {
+    role: Role::DaytimeRunning,
+    low_beam: Levels::Fixed(100),
+    high_beam: Levels::Off,
+    thermal_weight: 50,
+    driver_ma: 300,
+};
+
+/// Lights of a trailer, the current depends on the trailer
+pub const TRAILER: ChannelConfig = ChannelConfig {
+    role: Role::Trailer,
+    low_beam: Levels::Fixed(100),
+    high_beam: Levels::Fixed(100),
+    thermal_weight: 0,
+    driver_ma: 100,
+};
+
+impl ChannelConfig {
+    pub fn levels(&self, mode: Mode) -> Levels {
+        match mode {
+            Mode::LowBeam => self.low_beam,
+            Mode::HighBeam => self.high_beam,
+        }
+    }
+
+    /// Output in hundredths of a percent at [level], so that ratios are not rounded twice
+    fn raw_output(&self, profile: &Profile, mode: Mode, level: usize) -> u32 {
+        let table = |table: &[u8]| table.get(level).or(table.last()).map_or(0, |&it| it as u32);
+        match self.levels(mode) {
+            Levels::Off => 0,
+            Levels::Low => table(profile.low) * 100,
+            Levels::LowAux => table(profile.low_aux) * profile.low_aux_ratio,
+            Levels::High => table(profile.high) * 100,
+            Levels::Table(levels) => table(levels) * 100,
+            Levels::Fixed(_) if level == 0 => 0,
+            Levels::Fixed(output) => output as u32 * 100,
+        }
+    }
+
+    /// Output in percent at [level], reduced by [throttle] and by the weighted [heat] throttle
+    pub fn output(
+        &self,
+        profile: &Profile,
+        mode: Mode,
+        level: usize,
+        throttle: u32,
+        heat: u32,
+    ) -> u8 {
+        self.throttle(self.raw_output(profile, mode, level), throttle, heat)
+    }
+
+    /// Fixed [output] in percent, reduced like the [output](Self::output) of a level
+    pub fn throttled(&self, output: u8, throttle: u32, heat: u32) -> u8 {
+        self.throttle(output as u32 * 100, throttle, heat)
+    }
+
+    /// [raw] output in hundredths of a percent, reduced by [throttle] and the weighted [heat]
+    fn throttle(&self, raw: u32, throttle: u32, heat: u32) -> u8 {
+        let heat = 100 - (100 - heat.min(100)) * self.thermal_weight / 100;
+        (raw * throttle.min(heat) / 10000).min(100) as u8
+    }
+
+    /// Current in mA drawn from a battery at [voltage] mV with [output] in percent
+    pub fn current_ma(&self, voltage: u32, output: u32) -> u32 {
+        if voltage == 0 {
+            return 0;
+        }
+        current_ma(voltage, self.driver_ma, output)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.thermal_weight <= 100
+            && [self.low_beam, self.high_beam].iter().all(|it| match it {
+                Levels::Table(levels) => !levels.is_empty() && levels.iter().all(|&it| it <= 100),
+                Levels::Fixed(output) => *output <= 100,
+                _ => true,
+            })
+    }
+}
+
+/// LED and what it does
+#[derive(Clone, Copy)]
+pub struct Channel<'a> {
+    pub led: &'a dyn Led,
+    pub config: ChannelConfig,
+}
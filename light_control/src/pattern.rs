@@ -0,0 +1,242 @@
+use crate::output::Role;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct Step {
+    /// Output in percent, reduced by the throttle like the steady levels
+    pub output: u8,
+    /// Time in ms until the next step
+    pub duration: u16,
+}
+
+/// Steps of the channels with [role], up to 255
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct Track {
+    pub role: Role,
+    pub steps: &'static [Step],
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct StrobeConfig {
+    /// Flash time in ms
+    pub on: u16,
+    /// Dark time in ms
+    pub off: u16,
+    /// Output of the flashes in percent
+    pub output: u8,
+}
+
+/// 10 Hz at full output
+pub const STROBE: StrobeConfig = StrobeConfig {
+    on: 30,
+    off: 70,
+    output: 100,
+};
+
+impl Default for StrobeConfig {
+    fn default() -> Self {
+        STROBE
+    }
+}
+
+impl StrobeConfig {
+    pub fn is_valid(&self) -> bool {
+        self.on > 0 && self.off > 0 && self.output <= 100
+    }
+}
+
+/// Sequence of outputs per channel, played instead of the steady level.
+/// Channels without a track are off, [Role::Rear] channels are not changed.
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum Pattern {
+    /// Morse code for help with both beams
+    Sos,
+    /// Double flash every two seconds
+    Beacon,
+    /// Dimmed low beam with a double flash every two seconds, daytime running light on
+    DaytimePulse,
+    /// Low beam flashes
+    Strobe(StrobeConfig),
+    /// Own tracks, played once unless [looping], it ends with its first track
+    Custom {
+        tracks: &'static [Track],
+        looping: bool,
+    },
+}
+
+/// Cycled through by the buttons
+pub const PATTERNS: &[Pattern] = &[
+    Pattern::Sos,
+    Pattern::Beacon,
+    Pattern::DaytimePulse,
+    Pattern::Strobe(STROBE),
+];
+
+/// Length of a morse unit in ms
+const MORSE_UNIT: u16 = 200;
+const DOT: Step = Step {
+    output: 100,
+    duration: MORSE_UNIT,
+};
+const DASH: Step = Step {
+    output: 100,
+    duration: 3 * MORSE_UNIT,
+};
+const GAP: Step = Step {
+    output: 0,
+    duration: MORSE_UNIT,
+};
+const LETTER_GAP: Step = Step {
+    output: 0,
+    duration: 3 * MORSE_UNIT,
+};
+const WORD_GAP: Step = Step {
+    output: 0,
+    duration: 7 * MORSE_UNIT,
+};
+const SOS_STEPS: &[Step] = &[
+    DOT, GAP, DOT, GAP, DOT, LETTER_GAP, DASH, GAP, DASH, GAP, DASH, LETTER_GAP, DOT, GAP, DOT,
+    GAP, DOT, WORD_GAP,
+];
+const SOS_TRACKS: &[Track] = &[
+    Track {
+        role: Role::LowBeam,
+        steps: SOS_STEPS,
+    },
+    Track {
+        role: Role::HighBeam,
+        steps: SOS_STEPS,
+    },
+];
+
+const BEACON_TRACKS: &[Track] = &[Track {
+    role: Role::LowBeam,
+    steps: &[
+        Step {
+            output: 100,
+            duration: 50,
+        },
+        Step {
+            output: 0,
+            duration: 100,
+        },
+        Step {
+            output: 100,
+            duration: 50,
+        },
+        Step {
+            output: 0,
+            duration: 1800,
+        },
+    ],
+}];
+
+const DAYTIME_PULSE_TRACKS: &[Track] = &[
+    Track {
+        role: Role::LowBeam,
+        steps: &[
+            Step {
+                output: 30,
+                duration: 1700,
+            },
+            Step {
+                output: 100,
+                duration: 100,
+            },
+            Step {
+                output: 30,
+                duration: 100,
+            },
+            Step {
+                output: 100,
+                duration: 100,
+            },
+        ],
+    },
+    Track {
+        role: Role::DaytimeRunning,
+        steps: &[Step {
+            output: 100,
+            duration: 2000,
+        }],
+    },
+];
+
+impl Pattern {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::Sos => "sos",
+            Pattern::Beacon => "beacon",
+            Pattern::DaytimePulse => "daytime_pulse",
+            Pattern::Strobe(_) => "strobe",
+            Pattern::Custom { .. } => "custom",
+        }
+    }
+
+    /// One of [PATTERNS]
+    pub fn by_name(name: &str) -> Option<Pattern> {
+        PATTERNS.iter().find(|it| it.name() == name).copied()
+    }
+
+    /// Starts over after the last step, otherwise the steady level is restored
+    pub fn looping(&self) -> bool {
+        match self {
+            Pattern::Custom { looping, .. } => *looping,
+            _ => true,
+        }
+    }
+
+    /// Number of steps of the channels with [role], 0 if they are off
+    pub fn len(&self, role: Role) -> usize {
+        match self {
+            Pattern::Strobe(_) if role == Role::LowBeam => 2,
+            Pattern::Strobe(_) => 0,
+            _ => self.track(role).map_or(0, |it| it.len()),
+        }
+    }
+
+    /// Step [i] of the channels with [role], None if they are off or the pattern has ended
+    pub fn step(&self, role: Role, i: usize) -> Option<Step> {
+        let len = self.len(role);
+        if len == 0 || (i >= len && !self.looping()) {
+            return None;
+        }
+        let i = i % len;
+        match self {
+            Pattern::Strobe(config) if i == 0 => Some(Step {
+                output: config.output,
+                duration: config.on,
+            }),
+            Pattern::Strobe(config) => Some(Step {
+                output: 0,
+                duration: config.off,
+            }),
+            _ => self.track(role).map(|it| it[i]),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Pattern::Strobe(config) => config.is_valid(),
+            Pattern::Custom { tracks, .. } => tracks.iter().all(|it| {
+                !it.steps.is_empty()
+                    && it.steps.len() < 256
+                    && it
+                        .steps
+                        .iter()
+                        .all(|it| it.output <= 100 && it.duration > 0)
+            }),
+            _ => true,
+        }
+    }
+
+    fn track(&self, role: Role) -> Option<&'static [Step]> {
+        let tracks = match self {
+            Pattern::Sos => SOS_TRACKS,
+            Pattern::Beacon => BEACON_TRACKS,
+            Pattern::DaytimePulse => DAYTIME_PULSE_TRACKS,
+            Pattern::Strobe(_) => &[],
+            Pattern::Custom { tracks, .. } => tracks,
+        };
+        tracks.iter().find(|it| it.role == role).map(|it| it.steps)
+    }
+}
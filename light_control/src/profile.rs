@@ -0,0 +1,73 @@
+/// Power levels of both beams. Level 0 is off, all tables must have the same length.
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct Profile {
+    pub name: &'static str,
+    /// Low beam levels when the high beam is off
+    pub low: &'static [u8],
+    /// Low beam levels when the high beam is on
+    pub low_aux: &'static [u8],
+    /// High beam levels
+    pub high: &'static [u8],
+    /// Level used by [LightControl::jump_start]
+    pub init_level: usize,
+    /// Low beam output in percent of [low_aux] level while the high beam is on
+    pub low_aux_ratio: u32,
+    /// Patterns and the rear pulse may be used, StVZO forbids flashing lights
+    pub allows_flashing: bool,
+}
+
+impl Profile {
+    pub fn levels(&self) -> usize {
+        self.low.len()
+    }
+
+    pub fn max_level(&self) -> usize {
+        self.levels() - 1
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.levels() > 1
+            && self.low_aux.len() == self.levels()
+            && self.high.len() == self.levels()
+            && self.init_level < self.levels()
+    }
+
+    pub fn by_name(name: &str) -> Option<Profile> {
+        PROFILES.iter().find(|it| it.name == name).copied()
+    }
+}
+
+/// Balanced levels for city and commuting, low beam is dimmed while the high beam is on
+pub const COMMUTE: Profile = Profile {
+    name: "commute",
+    low: &[0, 15, 40, 65, 85],
+    low_aux: &[0, 15, 40, 65, 85],
+    high: &[0, 15, 40, 65, 85],
+    init_level: 3,
+    low_aux_ratio: 71,
+    allows_flashing: true,
+};
+
+/// Bright levels for unlit trails
+pub const TRAIL: Profile = Profile {
+    name: "trail",
+    low: &[0, 25, 50, 75, 100],
+    low_aux: &[0, 25, 45, 60, 80],
+    high: &[0, 60, 80, 90, 100],
+    init_level: 2,
+    low_aux_ratio: 100,
+    allows_flashing: true,
+};
+
+/// Low beam is limited to not dazzle oncoming traffic, high beam is only used for short periods
+pub const STVZO: Profile = Profile {
+    name: "stvzo",
+    low: &[0, 15, 30, 45, 60],
+    low_aux: &[0, 15, 30, 45, 60],
+    high: &[0, 30, 50, 70, 85],
+    init_level: 2,
+    low_aux_ratio: 100,
+    allows_flashing: false,
+};
+
+pub const PROFILES: &[Profile] = &[COMMUTE, TRAIL, STVZO];
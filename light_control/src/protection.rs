@@ -0,0 +1,121 @@
+use no_std_compat::cell::Cell;
+
+/// States ordered from a healthy to an empty battery
+#[derive(Clone, Debug, Eq, PartialEq, Copy, PartialOrd, Ord)]
+pub enum ProtectionState {
+    Normal,
+    /// Battery is getting low, the rider is warned by red blinks on entering it
+    Warning,
+    /// Only the lowest level is available, the "limp home" reserve
+    LowMode,
+    /// LEDs are off to protect the battery from a deep discharge. The state is latched, only
+    /// [BatteryProtection::override_lockout] leaves it.
+    Shutdown,
+}
+
+/// Thresholds are battery capacities in percent
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct ProtectionConfig {
+    pub warning: u32,
+    pub low_mode: u32,
+    pub shutdown: u32,
+    /// Capacity has to rise this much above a threshold to go back to the previous state,
+    /// the voltage recovers when the load drops
+    pub hysteresis: u32,
+    /// Time in ms of limp home after the lockout is overridden, the light shuts down again after it
+    pub reserve_time: u32,
+}
+
+impl Default for ProtectionConfig {
+    fn default() -> Self {
+        ProtectionConfig {
+            warning: 10,
+            low_mode: 5,
+            shutdown: 2,
+            hysteresis: 5,
+            reserve_time: 5 * 60 * 1000,
+        }
+    }
+}
+
+/// Protects the battery from a deep discharge
+pub struct BatteryProtection {
+    config: Cell<ProtectionConfig>,
+    state: Cell<ProtectionState>,
+    /// Remaining limp home time after an override
+    reserve_left: Cell<u32>,
+}
+
+impl BatteryProtection {
+    pub fn new(config: ProtectionConfig) -> Self {
+        BatteryProtection {
+            config: Cell::new(config),
+            state: Cell::new(ProtectionState::Normal),
+            reserve_left: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> ProtectionConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: ProtectionConfig) {
+        self.config.set(config);
+    }
+
+    pub fn state(&self) -> ProtectionState {
+        self.state.get()
+    }
+
+    /// Takes the battery [capacity] in percent measured [elapsed] ms after the previous call
+    pub fn update(&self, capacity: u32, elapsed: u32) -> ProtectionState {
+        let current = self.state.get();
+        let next = if current == ProtectionState::Shutdown {
+            current
+        } else if self.reserve_left.get() > 0 {
+            let left = self.reserve_left.get().saturating_sub(elapsed);
+            self.reserve_left.set(left);
+            if left == 0 {
+                ProtectionState::Shutdown
+            } else {
+                ProtectionState::LowMode
+            }
+        } else {
+            let worse = self.classify(capacity);
+            let better = self.classify(capacity.saturating_sub(self.config.get().hysteresis));
+            if worse > current {
+                worse
+            } else if better < current {
+                better
+            } else {
+                current
+            }
+        };
+        self.state.set(next);
+        next
+    }
+
+    /// Leaves [ProtectionState::Shutdown] for [ProtectionConfig::reserve_time] of limp home.
+    /// Returns false if the light was not locked out.
+    pub fn override_lockout(&self) -> bool {
+        if self.state.get() != ProtectionState::Shutdown {
+            return false;
+        }
+        self.state.set(ProtectionState::LowMode);
+        self.reserve_left.set(self.config.get().reserve_time.max(1));
+        true
+    }
+
+    fn classify(&self, capacity: u32) -> ProtectionState {
+        let config = self.config.get();
+        if capacity <= config.shutdown {
+            ProtectionState::Shutdown
+        } else if capacity <= config.low_mode {
+            ProtectionState::LowMode
+        } else if capacity <= config.warning {
+            ProtectionState::Warning
+        } else {
+            ProtectionState::Normal
+        }
+    }
+}
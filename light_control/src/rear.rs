@@ -0,0 +1,163 @@
+use no_std_compat::cell::Cell;
+
+use crate::bsp::brake::BrakeInput;
+use crate::bsp::led::MAX;
+use crate::bsp::motion::Accelerometer;
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum RearMode {
+    /// Constant output, the only mode allowed by StVZO
+    Steady,
+    /// Constant output with short flashes at full output
+    Pulse,
+}
+
+impl RearMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RearMode::Steady => "steady",
+            RearMode::Pulse => "pulse",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<RearMode> {
+        [RearMode::Steady, RearMode::Pulse]
+            .iter()
+            .find(|it| it.name() == name)
+            .copied()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct RearConfig {
+    pub mode: RearMode,
+    /// Output in percent while braking, 0 disables the brake light
+    pub brake_boost: u32,
+    /// Brake light stays on for this time in ms after the brake was released
+    pub brake_hold: u32,
+    /// Time in ms from one flash to the next in [RearMode::Pulse]
+    pub pulse_period: u32,
+    /// Length of a flash in ms
+    pub pulse_on: u32,
+}
+
+impl Default for RearConfig {
+    fn default() -> Self {
+        RearConfig {
+            mode: RearMode::Steady,
+            brake_boost: 100,
+            brake_hold: 1000,
+            pulse_period: 1000,
+            pulse_on: 100,
+        }
+    }
+}
+
+impl RearConfig {
+    pub fn is_valid(&self) -> bool {
+        self.brake_boost <= MAX && self.pulse_period > 0 && self.pulse_on <= self.pulse_period
+    }
+}
+
+/// Modulates the output of the rear channel by mode and brake.
+/// The rear light is off whenever its channel is, e.g. in the battery lockout.
+pub struct RearLight {
+    config: Cell<RearConfig>,
+    /// Time in ms the brake light stays on
+    hold: Cell<u32>,
+    /// Time in ms since the last flash
+    phase: Cell<u32>,
+}
+
+impl RearLight {
+    pub fn new(config: RearConfig) -> Self {
+        RearLight {
+            config: Cell::new(config),
+            hold: Cell::new(0),
+            phase: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> RearConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: RearConfig) {
+        self.config.set(config);
+        self.phase.set(0);
+    }
+
+    /// Brake light is on
+    pub fn is_braking(&self) -> bool {
+        self.hold.get() > 0 && self.config.get().brake_boost > 0
+    }
+
+    /// Advances pulse and brake light by [elapsed] ms, both start over while the light is off
+    pub fn update(&self, elapsed: u32, braking: bool, on: bool) {
+        let config = self.config.get();
+        if !on {
+            self.hold.set(0);
+            self.phase.set(0);
+            return;
+        }
+        if braking {
+            self.hold.set(config.brake_hold.max(1));
+        } else {
+            self.hold.set(self.hold.get().saturating_sub(elapsed));
+        }
+        self.phase
+            .set((self.phase.get() + elapsed) % config.pulse_period);
+    }
+
+    /// Output in percent of a channel with the [base] output in percent. The brake light
+    /// and flashes are reduced by the battery [throttle], flashes are suppressed unless
+    /// [pulse_allowed], e.g. in the StVZO profile.
+    pub fn output(&self, base: u32, throttle: u32, pulse_allowed: bool) -> u32 {
+        let config = self.config.get();
+        if base == 0 {
+            0
+        } else if self.is_braking() {
+            base.max(config.brake_boost * throttle / 100)
+        } else if config.mode == RearMode::Pulse
+            && pulse_allowed
+            && self.phase.get() < config.pulse_on
+        {
+            base.max(MAX * throttle / 100)
+        } else {
+            base
+        }
+    }
+}
+
+/// Brake detection by deceleration along the x axis, which points forward
+pub struct DecelerationBrake<'a> {
+    accelerometer: &'a dyn Accelerometer,
+    /// Deceleration in mg to detect
+    threshold: i32,
+    /// Low-passed deceleration in mg, bumps must not light the brake light
+    filtered: Cell<i32>,
+}
+
+impl<'a> DecelerationBrake<'a> {
+    pub fn new(accelerometer: &'a dyn Accelerometer, threshold: i32) -> Self {
+        DecelerationBrake {
+            accelerometer,
+            threshold,
+            filtered: Cell::new(0),
+        }
+    }
+}
+
+impl BrakeInput for DecelerationBrake<'_> {
+    /// Filter is updated on every call, so it is called once per check
+    fn is_braking(&self) -> bool {
+        let deceleration = match self.accelerometer.acceleration() {
+            Ok(acceleration) => -acceleration.x,
+            // a broken sensor must not light the brake light
+            Err(_) => 0,
+        };
+        self.filtered
+            .set((self.filtered.get() * 3 + deceleration) / 4);
+        self.filtered.get() >= self.threshold
+    }
+}
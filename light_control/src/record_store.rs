@@ -0,0 +1,322 @@
+use no_std_compat::cell::{Cell, RefCell};
+
+use crate::bsp::storage::{FlashPages, Storage, StorageError};
+
+/// Marks a page which was completely written by compaction
+const PAGE_MAGIC: u32 = 0x5253_4c31;
+const PAGE_HEADER_SIZE: usize = 8;
+const RECORD_HEADER_SIZE: usize = 4;
+const ALIGN: usize = 8;
+const MAX_RECORD_SIZE: usize = 32;
+pub const MAX_VALUE_SIZE: usize = MAX_RECORD_SIZE - RECORD_HEADER_SIZE;
+/// Key of erased memory, cannot be used
+const EMPTY: u8 = 0xff;
+
+/// Page which is currently appended to
+#[derive(Clone, Copy)]
+struct Active {
+    page: usize,
+    generation: u32,
+    end: usize,
+}
+
+/// Log-structured [Storage] on top of [FlashPages].
+///
+/// Records are appended to the active page, the last valid record of a key wins.
+/// When the page is full, latest records are copied to the next page, so erases
+/// are spread evenly across all pages. Each record is protected by a CRC, torn
+/// writes are ignored. The page header is written last, so a compaction interrupted
+/// by a power loss leaves the previous page active.
+///
+/// Record layout: `key, len, crc16 (LE), value, 0xff padding to 8 bytes`
+pub struct RecordStore<F: FlashPages> {
+    flash: F,
+    active: Cell<Option<Active>>,
+}
+
+impl<F: FlashPages> RecordStore<F> {
+    pub fn new(flash: F) -> Self {
+        debug_assert!(flash.page_count() >= 2, "compaction needs a spare page");
+        RecordStore {
+            flash,
+            active: Cell::new(None),
+        }
+    }
+
+    pub fn flash(&self) -> &F {
+        &self.flash
+    }
+
+    fn active(&self) -> Result<Active, StorageError> {
+        if let Some(active) = self.active.get() {
+            return Ok(active);
+        }
+
+        let newest = (0..self.flash.page_count())
+            .filter_map(|page| self.generation(page).map(|generation| (page, generation)))
+            .max_by_key(|(_, generation)| *generation);
+
+        let active = match newest {
+            Some((page, generation)) => Active {
+                page,
+                generation,
+                end: self.scan_end(page),
+            },
+            None => {
+                // blank or corrupted flash, start from scratch
+                self.flash.erase(0)?;
+                self.write_page_header(0, 0)?;
+                Active {
+                    page: 0,
+                    generation: 0,
+                    end: PAGE_HEADER_SIZE,
+                }
+            }
+        };
+        self.active.set(Some(active));
+        Ok(active)
+    }
+
+    fn generation(&self, page: usize) -> Option<u32> {
+        let mut header = [0u8; PAGE_HEADER_SIZE];
+        self.flash.read(page, 0, &mut header);
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let generation = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if magic == PAGE_MAGIC {
+            Some(generation)
+        } else {
+            None
+        }
+    }
+
+    fn write_page_header(&self, page: usize, generation: u32) -> Result<(), StorageError> {
+        let mut header = [0u8; PAGE_HEADER_SIZE];
+        header[..4].copy_from_slice(&PAGE_MAGIC.to_le_bytes());
+        header[4..].copy_from_slice(&generation.to_le_bytes());
+        self.flash.write(page, 0, &header)
+    }
+
+    /// Offset of the first erased record slot. Corrupted length means the rest
+    /// of the page cannot be trusted to be erased, so the page is considered full.
+    fn scan_end(&self, page: usize) -> usize {
+        let page_size = self.flash.page_size();
+        let mut offset = PAGE_HEADER_SIZE;
+        while offset + RECORD_HEADER_SIZE <= page_size {
+            let mut header = [0u8; RECORD_HEADER_SIZE];
+            self.flash.read(page, offset, &mut header);
+            if header[0] == EMPTY {
+                return offset;
+            }
+            let len = header[1] as usize;
+            if len > MAX_VALUE_SIZE || offset + record_size(len) > page_size {
+                return page_size;
+            }
+            offset += record_size(len);
+        }
+        page_size
+    }
+
+    /// Calls [f] with offset, key and value of every valid record in the page
+    fn for_each_record(&self, page: usize, end: usize, f: &mut dyn FnMut(usize, u8, &[u8])) {
+        let mut offset = PAGE_HEADER_SIZE;
+        let mut record = [0u8; MAX_RECORD_SIZE];
+        while offset < end {
+            self.flash
+                .read(page, offset, &mut record[..RECORD_HEADER_SIZE]);
+            let len = record[1] as usize;
+            if record[0] == EMPTY || len > MAX_VALUE_SIZE {
+                return;
+            }
+            let size = RECORD_HEADER_SIZE + len;
+            self.flash.read(
+                page,
+                offset + RECORD_HEADER_SIZE,
+                &mut record[RECORD_HEADER_SIZE..size],
+            );
+            let crc = u16::from_le_bytes([record[2], record[3]]);
+            if crc == record_crc(record[0], &record[RECORD_HEADER_SIZE..size]) {
+                f(offset, record[0], &record[RECORD_HEADER_SIZE..size]);
+            }
+            offset += record_size(len);
+        }
+    }
+
+    fn append(
+        &self,
+        page: usize,
+        offset: usize,
+        key: u8,
+        value: &[u8],
+    ) -> Result<(), StorageError> {
+        let size = record_size(value.len());
+        let mut record = [EMPTY; MAX_RECORD_SIZE];
+        record[0] = key;
+        record[1] = value.len() as u8;
+        record[2..4].copy_from_slice(&record_crc(key, value).to_le_bytes());
+        record[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + value.len()].copy_from_slice(value);
+        self.flash.write(page, offset, &record[..size])
+    }
+
+    /// Moves latest records to the next page and appends the new record there
+    fn compact(&self, active: Active, key: u8, value: &[u8]) -> Result<(), StorageError> {
+        let page_size = self.flash.page_size();
+        let next = (active.page + 1) % self.flash.page_count();
+        self.flash.erase(next)?;
+
+        // a bit per key which is moved already, so each key is only looked up once
+        let mut moved = [0u32; 8];
+        let mut end = PAGE_HEADER_SIZE;
+        let mut result = Ok(());
+        self.for_each_record(active.page, active.end, &mut |_, it, _| {
+            let (word, bit) = (it as usize / 32, 1 << (it % 32));
+            if result.is_err() || it == key || moved[word] & bit != 0 {
+                return;
+            }
+            moved[word] |= bit;
+            let mut latest = [0u8; MAX_VALUE_SIZE];
+            if let Some(len) = self.read(it, &mut latest) {
+                result = self.append(next, end, it, &latest[..len]);
+                end += record_size(len);
+            }
+        });
+        result?;
+
+        if end + record_size(value.len()) > page_size {
+            return Err(StorageError::Full);
+        }
+        self.append(next, end, key, value)?;
+        end += record_size(value.len());
+
+        let generation = active.generation.wrapping_add(1);
+        self.write_page_header(next, generation)?;
+        self.active.set(Some(Active {
+            page: next,
+            generation,
+            end,
+        }));
+        Ok(())
+    }
+}
+
+impl<F: FlashPages> Storage for RecordStore<F> {
+    fn read(&self, key: u8, buf: &mut [u8]) -> Option<usize> {
+        let active = self.active().ok()?;
+        let mut len = None;
+        self.for_each_record(active.page, active.end, &mut |_, it, value| {
+            if it == key {
+                let n = value.len().min(buf.len());
+                buf[..n].copy_from_slice(&value[..n]);
+                len = Some(value.len());
+            }
+        });
+        len
+    }
+
+    fn write(&self, key: u8, value: &[u8]) -> Result<(), StorageError> {
+        if key == EMPTY || value.len() > MAX_VALUE_SIZE {
+            return Err(StorageError::InvalidRecord);
+        }
+        let mut current = [0u8; MAX_VALUE_SIZE];
+        if self.read(key, &mut current) == Some(value.len()) && &current[..value.len()] == value {
+            // spare the flash
+            return Ok(());
+        }
+
+        let active = self.active()?;
+        let size = record_size(value.len());
+        if active.end + size <= self.flash.page_size() {
+            let result = self.append(active.page, active.end, key, value);
+            // a failed write may have left garbage behind, skip it anyway
+            self.active.set(Some(Active {
+                end: active.end + size,
+                ..active
+            }));
+            result
+        } else {
+            self.compact(active, key, value)
+        }
+    }
+}
+
+/// Flash which resides in memory, for simulation or testing
+pub struct MemoryFlash<const PAGE_SIZE: usize, const PAGES: usize> {
+    pages: RefCell<[[u8; PAGE_SIZE]; PAGES]>,
+    erase_counts: RefCell<[u32; PAGES]>,
+}
+
+impl<const PAGE_SIZE: usize, const PAGES: usize> MemoryFlash<PAGE_SIZE, PAGES> {
+    pub fn create() -> Self {
+        MemoryFlash {
+            pages: RefCell::new([[EMPTY; PAGE_SIZE]; PAGES]),
+            erase_counts: RefCell::new([0; PAGES]),
+        }
+    }
+
+    pub fn erase_count(&self, page: usize) -> u32 {
+        self.erase_counts.borrow()[page]
+    }
+}
+
+impl<const PAGE_SIZE: usize, const PAGES: usize> FlashPages for MemoryFlash<PAGE_SIZE, PAGES> {
+    fn page_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn page_count(&self) -> usize {
+        PAGES
+    }
+
+    fn read(&self, page: usize, offset: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.pages.borrow()[page][offset..offset + buf.len()]);
+    }
+
+    fn write(&self, page: usize, offset: usize, data: &[u8]) -> Result<(), StorageError> {
+        let mut pages = self.pages.borrow_mut();
+        let target = &mut pages[page][offset..offset + data.len()];
+        // same restrictions as the real flash
+        if !offset.is_multiple_of(ALIGN)
+            || !data.len().is_multiple_of(ALIGN)
+            || target.iter().any(|&b| b != EMPTY)
+        {
+            return Err(StorageError::Flash);
+        }
+        target.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&self, page: usize) -> Result<(), StorageError> {
+        self.pages.borrow_mut()[page] = [EMPTY; PAGE_SIZE];
+        self.erase_counts.borrow_mut()[page] += 1;
+        Ok(())
+    }
+}
+
+/// In-memory storage for simulation or testing
+pub type MemoryStorage = RecordStore<MemoryFlash<256, 2>>;
+
+impl MemoryStorage {
+    pub fn create() -> Self {
+        RecordStore::new(MemoryFlash::create())
+    }
+}
+
+fn record_size(len: usize) -> usize {
+    (RECORD_HEADER_SIZE + len).div_ceil(ALIGN) * ALIGN
+}
+
+fn record_crc(key: u8, value: &[u8]) -> u16 {
+    crc16(crc16(0xffff, &[key, value.len() as u8]), value)
+}
+
+/// CRC-16/CCITT-FALSE
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
@@ -0,0 +1,20 @@
+/// Each flash of [runtime_blinks] stands for this many minutes
+pub const MINUTES_PER_BLINK: u32 = 30;
+/// Longer runtimes are signalled with this many flashes
+pub const MAX_RUNTIME_BLINKS: u32 = 10;
+
+/// Minutes until [remaining_mah] is drained by [current_ma], None if nothing is drawn
+pub fn runtime_minutes(remaining_mah: u32, current_ma: u32) -> Option<u32> {
+    if current_ma == 0 {
+        None
+    } else {
+        Some((remaining_mah as u64 * 60 / current_ma as u64).min(u32::MAX as u64) as u32)
+    }
+}
+
+/// Number of flashes for [minutes], one per started [MINUTES_PER_BLINK]
+pub fn runtime_blinks(minutes: u32) -> u32 {
+    minutes
+        .div_ceil(MINUTES_PER_BLINK)
+        .clamp(1, MAX_RUNTIME_BLINKS)
+}
@@ -0,0 +1,147 @@
+use no_std_compat::cell::Cell;
+
+/// Min time in ms to measure pulses over, pulses are only seen when polled
+const MIN_MEASUREMENT: u32 = 1000;
+
+/// Min speed in km/h and the power level from there on, slowest first
+pub const DEFAULT_SPEED_LEVELS: &[(u32, usize)] = &[(0, 1), (10, 2), (20, 3), (30, 4)];
+
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct SpeedConfig {
+    /// Wheel circumference in mm
+    pub circumference: u32,
+    /// 1 for a reed switch, the number of poles for a hub dynamo
+    pub pulses_per_revolution: u32,
+    /// Without a pulse for this time in ms the bike is standing
+    pub timeout: u32,
+    pub levels: &'static [(u32, usize)],
+    /// High beam is switched on above this speed in km/h, 0 disables it
+    pub high_beam_above: u32,
+    /// Speed in km/h to leave a band or the high beam speed by, before they switch back
+    pub hysteresis: u32,
+}
+
+impl Default for SpeedConfig {
+    /// 28" wheel with a reed switch
+    fn default() -> Self {
+        SpeedConfig {
+            circumference: 2100,
+            pulses_per_revolution: 1,
+            timeout: 3000,
+            levels: DEFAULT_SPEED_LEVELS,
+            high_beam_above: 35,
+            hysteresis: 2,
+        }
+    }
+}
+
+impl SpeedConfig {
+    pub fn is_valid(&self) -> bool {
+        self.circumference > 0
+            && self.pulses_per_revolution > 0
+            && !self.levels.is_empty()
+            && self.levels.windows(2).all(|it| it[0].0 < it[1].0)
+            && self.levels.iter().all(|&(_, level)| level > 0)
+    }
+
+    /// Index in [levels] at [speed], the [current] band is widened by [hysteresis]
+    pub fn band(&self, speed: u32, current: Option<usize>) -> usize {
+        self.levels
+            .iter()
+            .enumerate()
+            .rposition(|(i, &(min_speed, _))| match current {
+                Some(current) if i > current => speed >= min_speed + self.hysteresis,
+                Some(current) if i == current => {
+                    speed + self.hysteresis >= min_speed || min_speed == 0
+                }
+                _ => speed >= min_speed,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Whether the high beam belongs on at [speed], [on] if it is on
+    pub fn high_beam(&self, speed: u32, on: bool) -> bool {
+        if self.high_beam_above == 0 {
+            false
+        } else if on {
+            speed + self.hysteresis >= self.high_beam_above
+        } else {
+            speed >= self.high_beam_above
+        }
+    }
+
+    /// Distance per pulse in mm
+    fn pulse_distance(&self) -> u32 {
+        self.circumference / self.pulses_per_revolution
+    }
+}
+
+/// Estimates the speed from the time between wheel pulses
+pub struct SpeedEstimator {
+    config: Cell<SpeedConfig>,
+    /// Pulse count and time of the last update with new pulses
+    last_pulse: Cell<Option<(u32, u32)>>,
+    /// Speed in mm/s
+    speed: Cell<u32>,
+}
+
+impl SpeedEstimator {
+    pub fn new(config: SpeedConfig) -> Self {
+        SpeedEstimator {
+            config: Cell::new(config),
+            last_pulse: Cell::new(None),
+            speed: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> SpeedConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: SpeedConfig) {
+        self.config.set(config);
+        self.last_pulse.set(None);
+        self.speed.set(0);
+    }
+
+    /// Speed in km/h
+    pub fn speed(&self) -> u32 {
+        (self.speed.get() as u64 * 36 / 10000) as u32
+    }
+
+    /// [pulses] is the total count of the wheel sensor at [now] in ms, both may wrap around
+    pub fn update(&self, now: u32, pulses: u32) {
+        let config = self.config.get();
+        let (last_count, last_time) = match self.last_pulse.get() {
+            Some(last) => last,
+            None => {
+                self.last_pulse.set(Some((pulses, now)));
+                return;
+            }
+        };
+        let elapsed = now.wrapping_sub(last_time).max(1);
+        let new_pulses = pulses.wrapping_sub(last_count);
+        if new_pulses > 0 && elapsed >= MIN_MEASUREMENT {
+            // u64, a reset counter or a long pause between reads makes a huge jump
+            let speed = new_pulses as u64 * config.pulse_distance() as u64 * 1000 / elapsed as u64;
+            // averaged, the time of the pulses is only known to a poll period
+            let previous = self.speed.get() as u64;
+            let speed = if previous == 0 {
+                speed
+            } else {
+                (previous + speed) / 2
+            };
+            self.speed.set(speed.min(u32::MAX as u64) as u32);
+            self.last_pulse.set(Some((pulses, now)));
+        } else if new_pulses > 0 {
+            // too short to measure
+        } else if elapsed >= config.timeout {
+            self.speed.set(0);
+        } else {
+            // the next pulse is overdue, the wheel is slower than one pulse so far
+            let max_speed = config.pulse_distance() as u64 * 1000 / elapsed as u64;
+            self.speed
+                .set((self.speed.get() as u64).min(max_speed) as u32);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::record_store::crc16;
+
+/// Start of a frame, frames are interleaved with console output in the same stream
+pub const MAGIC: [u8; 2] = [0xa5, 0x5a];
+/// Incremented when the layout of [Frame] changes
+pub const VERSION: u8 = 1;
+const PAYLOAD_SIZE: usize = 14;
+/// Magic, version, payload and crc16
+pub const FRAME_SIZE: usize = MAGIC.len() + 1 + PAYLOAD_SIZE + 2;
+
+/// Snapshot of the light state.
+///
+/// Layout: `magic, version, timestamp (u32), low, high, throttle, battery_mv (u16), capacity,
+/// temp (i16), rgb, queue_len, crc16 (of all preceding bytes)`. Numbers are little endian.
+#[derive(Clone, Debug, Eq, PartialEq, Copy, Default)]
+pub struct Frame {
+    /// Time of the [EDT] in ms
+    pub timestamp: u32,
+    /// Low beam output in percent
+    pub low: u8,
+    /// High beam output in percent
+    pub high: u8,
+    pub throttle: u8,
+    /// Measured battery voltage without load compensation, 0 if it cannot be read
+    pub battery_mv: u16,
+    /// Battery capacity in percent
+    pub capacity: u8,
+    /// Temperature in degrees Celsius, [i16::MIN] if it cannot be read
+    pub temp: i16,
+    /// Mask of [RED], [GREEN] and [BLUE]
+    pub rgb: u8,
+    /// Number of messages scheduled in the [EDT]
+    pub queue_len: u8,
+}
+
+impl Frame {
+    pub fn encode(&self) -> [u8; FRAME_SIZE] {
+        let mut frame = [0u8; FRAME_SIZE];
+        frame[0..2].copy_from_slice(&MAGIC);
+        frame[2] = VERSION;
+        frame[3..7].copy_from_slice(&self.timestamp.to_le_bytes());
+        frame[7] = self.low;
+        frame[8] = self.high;
+        frame[9] = self.throttle;
+        frame[10..12].copy_from_slice(&self.battery_mv.to_le_bytes());
+        frame[12] = self.capacity;
+        frame[13..15].copy_from_slice(&self.temp.to_le_bytes());
+        frame[15] = self.rgb;
+        frame[16] = self.queue_len;
+        let crc = crc16(0xffff, &frame[..FRAME_SIZE - 2]);
+        frame[FRAME_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+        frame
+    }
+
+    /// Returns None if magic, version or crc do not match
+    pub fn decode(frame: &[u8; FRAME_SIZE]) -> Option<Frame> {
+        let crc = u16::from_le_bytes([frame[FRAME_SIZE - 2], frame[FRAME_SIZE - 1]]);
+        if frame[0..2] != MAGIC
+            || frame[2] != VERSION
+            || crc != crc16(0xffff, &frame[..FRAME_SIZE - 2])
+        {
+            return None;
+        }
+        Some(Frame {
+            timestamp: u32::from_le_bytes([frame[3], frame[4], frame[5], frame[6]]),
+            low: frame[7],
+            high: frame[8],
+            throttle: frame[9],
+            battery_mv: u16::from_le_bytes([frame[10], frame[11]]),
+            capacity: frame[12],
+            temp: i16::from_le_bytes([frame[13], frame[14]]),
+            rgb: frame[15],
+            queue_len: frame[16],
+        })
+    }
+}
+
+/// Finds frames in a byte stream, bytes of anything else are skipped
+pub struct FrameDecoder {
+    buf: [u8; FRAME_SIZE],
+    len: usize,
+}
+
+impl FrameDecoder {
+    pub fn create() -> Self {
+        FrameDecoder {
+            buf: [0; FRAME_SIZE],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < FRAME_SIZE {
+            self.skip_garbage();
+            return None;
+        }
+
+        match Frame::decode(&self.buf) {
+            Some(frame) => {
+                self.len = 0;
+                Some(frame)
+            }
+            None => {
+                // magic may have been a part of the payload, look for the next one
+                self.drop_first();
+                self.skip_garbage();
+                None
+            }
+        }
+    }
+
+    /// Drops bytes until the buffer starts with (a part of) [MAGIC]
+    fn skip_garbage(&mut self) {
+        while self.len > 0 && !self.starts_with_magic() {
+            self.drop_first();
+        }
+    }
+
+    fn starts_with_magic(&self) -> bool {
+        let n = self.len.min(MAGIC.len());
+        self.buf[..n] == MAGIC[..n]
+    }
+
+    fn drop_first(&mut self) {
+        self.buf.copy_within(1..self.len, 0);
+        self.len -= 1;
+    }
+}
@@ -0,0 +1,141 @@
+use no_std_compat::cell::Cell;
+
+/// Max output of [ThermalRegulator], in percent
+const FULL_POWER: i32 = 100;
+
+/// Gains are in hundredths of a percent, [ThermalRegulator::update] is expected to be called
+/// with a fixed period, so integral and derivative are per update.
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub struct ThermalConfig {
+    /// Temperature in degrees Celsius which the regulator keeps the LEDs at or below
+    pub target: i32,
+    /// Output reduction per degree above [target]
+    pub kp: i32,
+    /// Output reduction per degree above [target], accumulated every update
+    pub ki: i32,
+    /// Output reduction per degree of temperature rise since the last update
+    pub kd: i32,
+    /// Lowest output in percent while not tripped, the light never goes dark because of heat
+    pub min_output: u32,
+    /// Max change of the output per update in percent, so the light does not visibly pump
+    pub max_step: u32,
+    /// Output is cut to 0 at this temperature, until it drops below [target] again
+    pub trip: i32,
+    /// Consecutive readings at or above [trip] needed to cut the output, a single spike is
+    /// ignored
+    pub trip_samples: u32,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        ThermalConfig {
+            target: 60,
+            kp: 400,
+            ki: 40,
+            kd: 200,
+            min_output: 10,
+            max_step: 5,
+            trip: 85,
+            trip_samples: 2,
+        }
+    }
+}
+
+/// PID regulator which throttles the LEDs to keep them at [ThermalConfig::target].
+/// Integral is only accumulated while the output is not saturated (anti-windup).
+pub struct ThermalRegulator {
+    config: Cell<ThermalConfig>,
+    integral: Cell<i32>,
+    prev_temp: Cell<Option<i32>>,
+    output: Cell<u32>,
+    tripped: Cell<bool>,
+    over_trip: Cell<u32>,
+}
+
+impl ThermalRegulator {
+    pub fn new(config: ThermalConfig) -> Self {
+        ThermalRegulator {
+            config: Cell::new(config),
+            integral: Cell::new(0),
+            prev_temp: Cell::new(None),
+            output: Cell::new(FULL_POWER as u32),
+            tripped: Cell::new(false),
+            over_trip: Cell::new(0),
+        }
+    }
+
+    pub fn config(&self) -> ThermalConfig {
+        self.config.get()
+    }
+
+    pub fn set_config(&self, config: ThermalConfig) {
+        self.config.set(config);
+    }
+
+    /// Last output in percent
+    pub fn output(&self) -> u32 {
+        self.output.get()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped.get()
+    }
+
+    /// Takes the current temperature and returns the output in percent
+    pub fn update(&self, temp: i32) -> u32 {
+        let config = self.config.get();
+        if temp >= config.trip {
+            let over_trip = self.over_trip.get().saturating_add(1);
+            self.over_trip.set(over_trip);
+            if over_trip < config.trip_samples && !self.tripped.get() {
+                // not confirmed yet, keep the spike out of the derivative and the integral
+                return self.output.get();
+            }
+        } else {
+            self.over_trip.set(0);
+        }
+        let prev_temp = self.prev_temp.replace(Some(temp)).unwrap_or(temp);
+
+        if temp >= config.trip {
+            self.tripped.set(true);
+        } else if self.tripped.get() && temp < config.target {
+            // recover from the bottom, the integral is stale
+            self.tripped.set(false);
+            self.integral.set(0);
+            self.output.set(config.min_output);
+        }
+        if self.tripped.get() {
+            self.output.set(0);
+            return 0;
+        }
+
+        let error = temp - config.target;
+        let min_output = config.min_output as i32;
+        // the output cannot go any lower, winding up would only delay the recovery
+        let saturated = error > 0 && self.output.get() as i32 <= min_output;
+        if !saturated {
+            self.integral
+                .set((self.integral.get() + error).clamp(0, integral_limit(config.ki)));
+        }
+
+        let reduction =
+            (config.kp * error + config.ki * self.integral.get() + config.kd * (temp - prev_temp))
+                / 100;
+        let wanted = (FULL_POWER - reduction).clamp(min_output, FULL_POWER);
+
+        let current = self.output.get() as i32;
+        let max_step = config.max_step as i32;
+        let output = wanted.clamp(current - max_step, current + max_step) as u32;
+        self.output.set(output);
+        output
+    }
+}
+
+/// Integral which alone throttles the output to 0
+fn integral_limit(ki: i32) -> i32 {
+    if ki > 0 {
+        FULL_POWER * 100 / ki
+    } else {
+        0
+    }
+}
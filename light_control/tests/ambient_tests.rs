@@ -0,0 +1,141 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::ambient::{AutoBrightness, AutoConfig};
+    use light_control::bsp::adc::SensorError;
+    use light_control::bsp::ambient::Lux;
+    use light_control::control::BATTERY_CHECK_PERIOD;
+
+    use crate::common::{with_bench, Bench, Setup};
+
+    #[test]
+    fn bands_have_hysteresis() {
+        let auto = AutoBrightness::new(AutoConfig {
+            smoothing: 0,
+            hold: 0,
+            ..AutoConfig::default()
+        });
+        assert_eq!(auto.level(), None);
+        assert_eq!(auto.update(Ok(Lux(40)), 500), Some(2));
+        assert_eq!(auto.update(Ok(Lux(22)), 500), Some(2));
+        assert_eq!(auto.update(Ok(Lux(20)), 500), Some(3));
+        assert_eq!(auto.update(Ok(Lux(38)), 500), Some(3));
+        assert_eq!(auto.update(Ok(Lux(40)), 500), Some(2));
+        assert_eq!(auto.update(Err(SensorError::Adc), 500), Some(2));
+        // smoothed on a log scale with limited precision
+        assert!(auto.lux().is_some_and(|it| it.0.abs_diff(40) <= 1));
+    }
+
+    #[test]
+    fn auto_needs_a_sensor() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(!bench.light_control.set_auto(true));
+            assert!(!bench.light_control.auto());
+        });
+    }
+
+    #[test]
+    fn level_follows_dusk_into_night() {
+        with_bench(ambient_light(), &|bench| {
+            bench.lux.set(10000);
+            assert!(bench.light_control.set_auto(true));
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.power_level(), 1);
+
+            bench.lux.set(100);
+            bench.advance_time(15000);
+            assert_eq!(bench.light_control.power_level(), 2);
+            bench.lux.set(1);
+            bench.advance_time(15000);
+            assert_eq!(bench.light_control.power_level(), 4);
+        });
+    }
+
+    #[test]
+    fn short_tunnel_is_ignored() {
+        with_bench(ambient_light(), &|bench| {
+            bench.lux.set(10000);
+            bench.light_control.set_auto(true);
+            bench.advance_time(1000);
+            bench.lux.set(5);
+            let mut changes = level_changes(&bench, 2000);
+            bench.lux.set(10000);
+            changes += level_changes(&bench, 10000);
+            assert_eq!(bench.light_control.power_level(), 1);
+            assert_eq!(changes, 0);
+
+            bench.lux.set(5);
+            bench.advance_time(20000);
+            assert_eq!(bench.light_control.power_level(), 3);
+        });
+    }
+
+    #[test]
+    fn streetlights_do_not_flicker() {
+        with_bench(ambient_light(), &|bench| {
+            bench.lux.set(1);
+            bench.light_control.set_auto(true);
+            bench.advance_time(1000);
+            let mut levels = Vec::new();
+            for _ in 0..30 {
+                // passing a streetlight every two seconds
+                bench.lux.set(20);
+                bench.advance_time(1000);
+                bench.lux.set(4);
+                bench.advance_time(1000);
+                levels.push(bench.light_control.power_level());
+            }
+            levels.dedup();
+            assert!(levels.len() <= 2, "{:?}", levels);
+            assert_eq!(levels.last(), Some(&3));
+        });
+    }
+
+    #[test]
+    fn clicks_bias_the_level_and_manual_levels_leave_auto() {
+        with_bench(ambient_light(), &|bench| {
+            bench.lux.set(100);
+            bench.light_control.set_auto(true);
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.power_level(), 2);
+
+            bench.click(bench.plus);
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.auto_bias(), 1);
+            assert_eq!(bench.light_control.power_level(), 3);
+            // the bias stays as the ambient light changes
+            bench.lux.set(1);
+            bench.advance_time(15000);
+            assert_eq!(bench.light_control.power_level(), 4);
+
+            assert!(bench.light_control.set_power_level(2));
+            assert!(!bench.light_control.auto());
+            bench.lux.set(10000);
+            bench.advance_time(15000);
+            assert_eq!(bench.light_control.power_level(), 2);
+        });
+    }
+
+    fn ambient_light() -> Setup<'static> {
+        Setup {
+            ambient_light: true,
+            ..Setup::default()
+        }
+    }
+
+    /// Advances [time] and returns how often the level changed, checked every
+    /// [BATTERY_CHECK_PERIOD]
+    fn level_changes(bench: &Bench, time: u32) -> u32 {
+        let mut level = bench.light_control.power_level();
+        let mut changes = 0;
+        for _ in 0..time / BATTERY_CHECK_PERIOD {
+            bench.advance_time(BATTERY_CHECK_PERIOD);
+            if bench.light_control.power_level() != level {
+                level = bench.light_control.power_level();
+                changes += 1;
+            }
+        }
+        changes
+    }
+}
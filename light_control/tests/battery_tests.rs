@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use light_control::battery::{BatteryModel, Chemistry};
+
+    const LI_ION_2S: BatteryModel = BatteryModel {
+        chemistry: Chemistry::LiIon,
+        cells: 2,
+    };
+
+    #[test]
+    fn percentages() {
+        let voltages = [
+            8400, 8200, 8100, 8000, 7900, 7800, 7700, 7640, 7600, 7540, 7500, 7460, 7440, 7420,
+            7400, 7380, 7360, 7300, 7280, 7200, 6920, 6600, 6300, 6000,
+        ];
+
+        let expected = [
+            100, 95, 90, 85, 80, 75, 70, 63, 59, 53, 49, 45, 40, 35, 30, 25, 20, 14, 13, 11, 7, 4,
+            2, 0,
+        ];
+        let res: Vec<u32> = voltages.iter().map(|it| LI_ION_2S.capacity(*it)).collect();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn voltages_out_of_range_are_clamped() {
+        assert_eq!(LI_ION_2S.capacity(0), 0);
+        assert_eq!(LI_ION_2S.capacity(5999), 0);
+        assert_eq!(LI_ION_2S.capacity(u32::MAX), 100);
+    }
+
+    #[test]
+    fn cell_count_scales_the_curve() {
+        for cells in 1..=4 {
+            let model = BatteryModel {
+                chemistry: Chemistry::LiIon,
+                cells,
+            };
+            assert!(model.is_valid());
+            assert_eq!(model.capacity(3850 * cells as u32), 70);
+            assert_eq!(model.full_mv(), 4150 * cells as u32);
+            assert_eq!(model.empty_mv(), 3000 * cells as u32);
+        }
+        assert!(!BatteryModel {
+            chemistry: Chemistry::LiIon,
+            cells: 0
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn capacity_never_increases_when_voltage_drops() {
+        for chemistry in [Chemistry::LiIon, Chemistry::LiFePo4] {
+            let model = BatteryModel {
+                chemistry,
+                cells: 3,
+            };
+            let mut prev = 100;
+            for mv in (0..model.full_mv() + 500).rev() {
+                let capacity = model.capacity(mv);
+                assert!(capacity <= prev, "{:?} {} mV", chemistry, mv);
+                prev = capacity;
+            }
+            assert_eq!(prev, 0);
+        }
+    }
+
+    #[test]
+    fn lifepo4_has_its_own_curve() {
+        let model = BatteryModel {
+            chemistry: Chemistry::LiFePo4,
+            cells: 4,
+        };
+        assert_eq!(model.capacity(13600), 100);
+        assert_eq!(model.capacity(13200), 70);
+        assert_eq!(model.capacity(10000), 0);
+        assert_eq!(Chemistry::by_name("lifepo4"), Some(Chemistry::LiFePo4));
+    }
+}
@@ -0,0 +1,311 @@
+//! Bench shared by the integration tests, each of them uses only a part of it
+#![allow(dead_code)]
+
+use std::cell::Cell;
+
+use light_control::bsp::adc::{Decidegrees, Millivolts, SensorError, Sensors};
+use light_control::bsp::ambient::{AmbientLight, Lux};
+use light_control::bsp::brake::BrakeInput;
+use light_control::bsp::led::Led;
+use light_control::bsp::pin::Pin;
+use light_control::bsp::rgb::Rgb;
+use light_control::bsp::wheel::WheelSensor;
+use light_control::control::{Action, LightControl, BUTTON_CHECK_PERIOD};
+use light_control::edt::EDT;
+use light_control::gestures::{LONG_CLICK_THRESHOLD, MULTI_CLICK_WINDOW};
+use light_control::output::REAR;
+use light_control::profile::COMMUTE;
+use light_control::record_store::MemoryStorage;
+use light_control::speed::SpeedConfig;
+use sensor_replay::{CsvAccelerometer, CsvLightSensor};
+
+pub type TestLightControl<'a> = LightControl<'a, TestPin<'a>, TestPin<'a>, TestPin<'a>>;
+
+/// Optional inputs of the bench, none of them is connected by default
+#[derive(Default)]
+pub struct Setup<'a> {
+    /// Accelerations replayed from a CSV recording, see [CsvAccelerometer]
+    pub motion: Option<&'a str>,
+    /// Light in front of the bike replayed from a CSV recording, see [CsvLightSensor]
+    pub forward_light: Option<&'a str>,
+    /// Ambient light sensor reading [Bench::lux]
+    pub ambient_light: bool,
+    /// Wheel sensor turning at [Bench::speed]
+    pub wheel: bool,
+    /// Brake input reading [Bench::braking]
+    pub brake: bool,
+    /// [REAR] channel on [Bench::rear]
+    pub rear: bool,
+    /// Called right after the start, before the startup animation is done
+    pub configure: Option<&'a dyn Fn(&TestLightControl)>,
+}
+
+pub struct Bench<'a> {
+    pub light_control: &'a TestLightControl<'a>,
+    pub edt: &'a EDT<Action>,
+    pub plus: &'a Cell<bool>,
+    pub minus: &'a Cell<bool>,
+    pub toggle: &'a Cell<bool>,
+    pub low_beam: &'a Cell<u32>,
+    pub high_beam: &'a Cell<u32>,
+    /// Connected with [Setup::rear], otherwise free to be added as a channel
+    pub rear: &'a TestLed,
+    /// Free to be added as a channel
+    pub drl: &'a TestLed,
+    pub rgb: &'a Cell<u8>,
+    pub voltage: &'a Cell<Result<Millivolts, SensorError>>,
+    pub temp: &'a Cell<Result<Decidegrees, SensorError>>,
+    pub lux: &'a Cell<u32>,
+    /// Current speed in km/h
+    pub speed: &'a Cell<u32>,
+    pub braking: &'a Cell<bool>,
+}
+
+impl Bench<'_> {
+    pub fn advance_time(&self, time: u32) {
+        self.edt
+            .advance_time_by(time, &|msg| self.light_control.process_message(msg));
+    }
+
+    /// Returns when the gesture is reported
+    pub fn click(&self, pin: &Cell<bool>) {
+        self.press(pin);
+        self.advance_time(BUTTON_CHECK_PERIOD + MULTI_CLICK_WINDOW);
+    }
+
+    /// Returns right after the release, when the gesture is reported
+    pub fn triple_click(&self, pin: &Cell<bool>) {
+        for _ in 0..3 {
+            self.press(pin);
+            self.advance_time(BUTTON_CHECK_PERIOD);
+        }
+    }
+
+    /// Holds [pin] past [LONG_CLICK_THRESHOLD], returns after the release
+    pub fn long_click(&self, pin: &Cell<bool>) {
+        pin.set(true);
+        self.advance_time(LONG_CLICK_THRESHOLD + 2 * BUTTON_CHECK_PERIOD);
+        pin.set(false);
+        self.advance_time(BUTTON_CHECK_PERIOD);
+    }
+
+    pub fn click_and_hold(&self, pin: &Cell<bool>) {
+        self.press(pin);
+        self.advance_time(2 * BUTTON_CHECK_PERIOD);
+        pin.set(true);
+        self.advance_time(LONG_CLICK_THRESHOLD + BUTTON_CHECK_PERIOD);
+        pin.set(false);
+        self.advance_time(BUTTON_CHECK_PERIOD);
+    }
+
+    /// Changes the speed to [speed] in km/h and rides on for [time] ms
+    pub fn ride(&self, speed: u32, time: u32) {
+        self.speed.set(speed);
+        self.advance_time(time);
+    }
+
+    fn press(&self, pin: &Cell<bool>) {
+        pin.set(true);
+        self.advance_time(2 * BUTTON_CHECK_PERIOD);
+        pin.set(false);
+    }
+}
+
+/// Steady on level 3 of [COMMUTE] with the low beam, 2 seconds after the start
+pub fn with_bench(setup: Setup, block: &dyn Fn(Bench)) {
+    let plus = Cell::new(false);
+    let minus = Cell::new(false);
+    let toggle = Cell::new(false);
+    let led = TestLed::default();
+    let led_high = TestLed::default();
+    let rear = TestLed::default();
+    let drl = TestLed::default();
+    let rgb = TestRgb::default();
+    let sensors = TestSensors::default();
+    let lux = Cell::new(0);
+    let speed = Cell::new(0);
+    let braking = Cell::new(false);
+    let storage = MemoryStorage::create();
+    let edt = EDT::create();
+    let clock = || edt.now();
+    let accelerometer = setup
+        .motion
+        .map(|csv| CsvAccelerometer::create(csv, &clock));
+    let forward_light = setup
+        .forward_light
+        .map(|csv| CsvLightSensor::create(csv, &clock));
+    let ambient_light = TestAmbientLight { lux: &lux };
+    let wheel = TestWheel::create(&speed, &clock);
+    let brake = TestBrake { braking: &braking };
+    let light_control = LightControl::new(
+        TestPin { down: &plus },
+        TestPin { down: &minus },
+        TestPin { down: &toggle },
+        &led,
+        &led_high,
+        &rgb,
+        &edt,
+        &sensors,
+        &storage,
+        COMMUTE,
+    );
+    if let Some(accelerometer) = &accelerometer {
+        light_control.set_accelerometer(accelerometer);
+    }
+    if let Some(forward_light) = &forward_light {
+        light_control.set_forward_light(forward_light);
+    }
+    if setup.ambient_light {
+        light_control.set_ambient_light(&ambient_light);
+    }
+    if setup.wheel {
+        light_control.set_wheel_sensor(&wheel);
+    }
+    if setup.brake {
+        light_control.set_brake_input(&brake);
+    }
+    if setup.rear {
+        light_control.add_channel(&rear, REAR);
+    }
+    light_control.start();
+    light_control.jump_start();
+    if let Some(configure) = setup.configure {
+        configure(&light_control);
+    }
+    let bench = Bench {
+        light_control: &light_control,
+        edt: &edt,
+        plus: &plus,
+        minus: &minus,
+        toggle: &toggle,
+        low_beam: &led.output,
+        high_beam: &led_high.output,
+        rear: &rear,
+        drl: &drl,
+        rgb: &rgb.rgb,
+        voltage: &sensors.voltage,
+        temp: &sensors.temp,
+        lux: &lux,
+        speed: &speed,
+        braking: &braking,
+    };
+    bench.advance_time(2000);
+    block(bench);
+}
+
+pub struct TestPin<'a> {
+    pub down: &'a Cell<bool>,
+}
+
+impl Pin for TestPin<'_> {
+    fn is_down(&self) -> bool {
+        self.down.get()
+    }
+}
+
+#[derive(Default)]
+pub struct TestLed {
+    pub output: Cell<u32>,
+}
+
+impl Led for TestLed {
+    fn set(&self, duty_cycle: u32) {
+        self.output.set(duty_cycle);
+    }
+
+    fn get(&self) -> u32 {
+        self.output.get()
+    }
+}
+
+#[derive(Default)]
+pub struct TestRgb {
+    pub rgb: Cell<u8>,
+}
+
+impl Rgb for TestRgb {
+    fn set_rgb(&self, rgb: u8) {
+        self.rgb.set(rgb);
+    }
+
+    fn get_rgb(&self) -> u8 {
+        self.rgb.get()
+    }
+}
+
+/// Full 2S battery at room temperature
+pub struct TestSensors {
+    pub voltage: Cell<Result<Millivolts, SensorError>>,
+    pub temp: Cell<Result<Decidegrees, SensorError>>,
+}
+
+impl Default for TestSensors {
+    fn default() -> Self {
+        TestSensors {
+            voltage: Cell::new(Ok(Millivolts(8400))),
+            temp: Cell::new(Ok(Decidegrees::from_celsius(20))),
+        }
+    }
+}
+
+impl Sensors for TestSensors {
+    fn battery_voltage(&self) -> Result<Millivolts, SensorError> {
+        self.voltage.get()
+    }
+
+    fn temp(&self) -> Result<Decidegrees, SensorError> {
+        self.temp.get()
+    }
+}
+
+pub struct TestAmbientLight<'a> {
+    pub lux: &'a Cell<u32>,
+}
+
+impl AmbientLight for TestAmbientLight<'_> {
+    fn lux(&self) -> Result<Lux, SensorError> {
+        Ok(Lux(self.lux.get()))
+    }
+}
+
+pub struct TestBrake<'a> {
+    pub braking: &'a Cell<bool>,
+}
+
+impl BrakeInput for TestBrake<'_> {
+    fn is_braking(&self) -> bool {
+        self.braking.get()
+    }
+}
+
+/// Reed switch on a wheel with the default circumference
+pub struct TestWheel<'a> {
+    /// Speed in km/h
+    speed: &'a Cell<u32>,
+    clock: &'a dyn Fn() -> u32,
+    /// Distance in µm until [since]
+    distance: Cell<u64>,
+    since: Cell<u32>,
+}
+
+impl<'a> TestWheel<'a> {
+    pub fn create(speed: &'a Cell<u32>, clock: &'a dyn Fn() -> u32) -> Self {
+        TestWheel {
+            speed,
+            clock,
+            distance: Cell::new(0),
+            since: Cell::new(clock()),
+        }
+    }
+}
+
+impl WheelSensor for TestWheel<'_> {
+    fn pulses(&self) -> u32 {
+        let now = (self.clock)();
+        let time = now.wrapping_sub(self.since.replace(now)) as u64;
+        let distance = self.distance.get() + self.speed.get() as u64 * time * 1_000_000 / 3600;
+        self.distance.set(distance);
+        let circumference = SpeedConfig::default().circumference as u64 * 1000;
+        (distance / circumference) as u32
+    }
+}
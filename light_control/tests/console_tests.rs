@@ -0,0 +1,374 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use light_control::bsp::rgb::{Rgb, BLUE};
+    use light_control::bsp::serial::Serial;
+    use light_control::console::{parse, Command, CommandError, Console, GestureTiming};
+    use light_control::control::LightControl;
+    use light_control::edt::EDT;
+    use light_control::pattern::{Pattern, StrobeConfig};
+    use light_control::profile::{COMMUTE, TRAIL};
+    use light_control::record_store::MemoryStorage;
+    use light_control::telemetry::{Frame, FrameDecoder};
+
+    use crate::common::{TestLed, TestPin, TestRgb, TestSensors};
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(parse("level 3"), Ok(Command::Level(3)));
+        assert_eq!(parse("  beam   high "), Ok(Command::Beam { high: true }));
+        assert_eq!(parse("profile trail"), Ok(Command::Profile(Some(TRAIL))));
+        assert_eq!(parse("profile"), Ok(Command::Profile(None)));
+        assert_eq!(
+            parse("pattern sos"),
+            Ok(Command::Pattern(Some(Pattern::Sos)))
+        );
+        assert_eq!(
+            parse("pattern strobe 20 80"),
+            Ok(Command::Pattern(Some(Pattern::Strobe(StrobeConfig {
+                on: 20,
+                off: 80,
+                output: 100
+            }))))
+        );
+        assert_eq!(
+            parse("gesture long_click 800"),
+            Ok(Command::Gesture(Some((GestureTiming::LongClick, 800))))
+        );
+    }
+
+    #[test]
+    fn invalid_commands_are_rejected() {
+        assert_eq!(parse("disco"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse("level"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("level max"), Err(CommandError::InvalidArgument));
+        assert_eq!(parse("beam sideways"), Err(CommandError::InvalidArgument));
+        assert_eq!(parse("status now"), Err(CommandError::TooManyArguments));
+        assert_eq!(
+            parse("gesture hold_repeat 0"),
+            Err(CommandError::InvalidArgument)
+        );
+        assert_eq!(parse("telemetry 9"), Err(CommandError::InvalidArgument));
+        assert_eq!(parse("telemetry 60001"), Err(CommandError::InvalidArgument));
+        assert_eq!(parse("telemetry 0"), Ok(Command::Telemetry(0)));
+    }
+
+    #[test]
+    fn status_is_tab_separated() {
+        with_console(&|bench| {
+            assert_eq!(
+                bench.send("status\n"),
+                "level\t3\nbeam\tlow\nprofile\tcommute\nthrottle\t100\nmotion\tmoving\nok\n"
+            );
+        });
+    }
+
+    #[test]
+    fn commands_may_be_split_across_packets() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("te"), "");
+            assert_eq!(bench.send("mp\r"), "temp\t20\nok\n");
+            // \n of \r\n is ignored
+            assert_eq!(
+                bench.send("\nbattery\r\n"),
+                "voltage\t8400\ncompensated\t8540\ncapacity\t100\nok\n"
+            );
+        });
+    }
+
+    #[test]
+    fn battery_model_is_changed() {
+        with_console(&|bench| {
+            assert_eq!(
+                bench.send("battery lifepo4 5\n"),
+                "error\tinvalid argument\n"
+            );
+            assert_eq!(bench.send("battery lifepo4 2\n"), "ok\n");
+            // 8400 mV is above a full 2S LiFePO4 pack
+            assert_eq!(
+                bench.send("battery\n"),
+                "voltage\t8400\ncompensated\t8540\ncapacity\t100\nok\n"
+            );
+            assert_eq!(bench.send("battery li-ion 3\n"), "ok\n");
+            // empty battery shut the LEDs down, there is no load to compensate
+            assert_eq!(
+                bench.send("battery\n"),
+                "voltage\t8400\ncompensated\t8400\ncapacity\t0\nok\n"
+            );
+        });
+    }
+
+    #[test]
+    fn fuel_gauge_is_printed() {
+        with_console(&|bench| {
+            let output = bench.send("gauge\n");
+            assert!(output.starts_with("remaining\t3399\ncapacity\t99\n"));
+            assert!(output.ends_with("resistance\t320\nok\n"));
+        });
+    }
+
+    #[test]
+    fn runtime_is_printed_for_every_level() {
+        with_console(&|bench| {
+            let output = bench.send("runtime\n");
+            let lines: Vec<&str> = output.lines().collect();
+            assert_eq!(lines.len(), 6);
+            let minutes = |line: &str| line.split('\t').nth(1).unwrap().parse::<u32>().unwrap();
+            assert_eq!(minutes(lines[0]), minutes(lines[3]));
+            assert!(lines[3].starts_with("3\t"));
+            assert!(minutes(lines[1]) > minutes(lines[4]));
+            assert_eq!(lines[5], "ok");
+        });
+    }
+
+    #[test]
+    fn outputs_are_printed_by_role() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("beam high\n"), "ok\n");
+            assert_eq!(
+                bench.send("outputs\n"),
+                format!(
+                    "low_beam\t{}\nhigh_beam\t{}\nok\n",
+                    bench.low_beam.get(),
+                    COMMUTE.high[3]
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn rear_mode_is_changed() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("rear blink\n"), "error\tinvalid argument\n");
+            assert_eq!(bench.send("rear pulse\n"), "ok\n");
+            assert_eq!(bench.send("rear\n"), "mode\tpulse\nbraking\t0\nok\n");
+        });
+    }
+
+    #[test]
+    fn auto_mode_needs_a_sensor() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("auto on\n"), "error\trejected\n");
+            assert_eq!(bench.send("auto\n"), "auto\toff\nlux\t-\nbias\t0\nok\n");
+        });
+    }
+
+    #[test]
+    fn speed_mode_needs_a_sensor() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("speed on\n"), "error\trejected\n");
+            assert_eq!(bench.send("speed fast\n"), "error\tinvalid argument\n");
+            assert_eq!(bench.send("speed\n"), "speed\t0\nmode\toff\nok\n");
+        });
+    }
+
+    #[test]
+    fn auto_dipping_needs_a_sensor() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("dipping on\n"), "error\trejected\n");
+            assert_eq!(bench.send("dipping\n"), "dipping\toff\ndipped\t0\nok\n");
+        });
+    }
+
+    #[test]
+    fn pattern_is_played_and_stopped() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("pattern disco\n"), "error\tinvalid argument\n");
+            assert_eq!(bench.send("pattern strobe 0 80\n"), "error\trejected\n");
+            assert_eq!(bench.send("pattern beacon\n"), "ok\n");
+            assert!(bench
+                .send("pattern\n")
+                .starts_with("pattern\tbeacon\navailable\tsos\n"));
+            assert_eq!(bench.send("pattern off\n"), "ok\n");
+            assert!(bench.send("pattern\n").starts_with("pattern\toff\n"));
+        });
+    }
+
+    #[test]
+    fn lock_switches_off_until_unlocked() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("lock on\n"), "ok\n");
+            assert_eq!(bench.send("locked\n"), "error\tunknown command, try help\n");
+            assert_eq!(bench.send("lock\n"), "locked\t1\nok\n");
+            assert_eq!(bench.send("level 2\n"), "error\trejected\n");
+            assert_eq!(bench.send("lock off\n"), "ok\n");
+            assert_eq!(bench.send("lock\n"), "locked\t0\nok\n");
+        });
+    }
+
+    #[test]
+    fn lock_without_change_is_rejected() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("lock off\n"), "error\trejected\n");
+            assert_eq!(bench.send("lock on\n"), "ok\n");
+            assert_eq!(bench.send("lock on\n"), "error\trejected\n");
+            assert_eq!(bench.send("lock\n"), "locked\t1\nok\n");
+        });
+    }
+
+    #[test]
+    fn level_changes_brightness() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("level 1\n"), "ok\n");
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert_eq!(bench.send("level 5\n"), "error\trejected\n");
+            assert_eq!(bench.send("level 0\n"), "error\trejected\n");
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+        });
+    }
+
+    #[test]
+    fn beam_switches_high_beam() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("beam high\n"), "ok\n");
+            assert_eq!(bench.high_beam.get(), COMMUTE.high[3] as u32);
+            assert_eq!(bench.send("beam low\n"), "ok\n");
+            assert_eq!(bench.high_beam.get(), 0);
+        });
+    }
+
+    #[test]
+    fn profile_is_switched() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("profile trail\n"), "ok\n");
+            assert_eq!(bench.low_beam.get(), TRAIL.low[3] as u32);
+            assert_eq!(
+                bench.send("profile\n"),
+                "profile\ttrail\navailable\tcommute\navailable\ttrail\navailable\tstvzo\nok\n"
+            );
+        });
+    }
+
+    #[test]
+    fn gesture_timings_are_changed() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("gesture multi_click 200\n"), "ok\n");
+            assert_eq!(
+                bench.send("gesture long_click 0\n"),
+                "error\tinvalid argument\n"
+            );
+            assert_eq!(
+                bench.send("gesture\n"),
+                "long_click\t1000\nmulti_click\t200\nhold_repeat\t500\nok\n"
+            );
+        });
+    }
+
+    #[test]
+    fn errors_are_replied() {
+        with_console(&|bench| {
+            assert_eq!(bench.send("disco\n"), "error\tunknown command, try help\n");
+            assert_eq!(bench.send(&"a".repeat(100)), "");
+            assert_eq!(
+                bench.send("\nstatus\n").lines().next(),
+                Some("error\tline too long")
+            );
+            assert!(bench.send("help\n").starts_with("help\tlists commands\n"));
+        });
+    }
+
+    #[test]
+    fn telemetry_is_streamed() {
+        with_console(&|bench| {
+            let output = bench.send_raw("telemetry 500\n");
+            assert!(output.starts_with(b"ok\n"));
+
+            let mut decoder = FrameDecoder::create();
+            let frames: Vec<Frame> = output.iter().filter_map(|&b| decoder.push(b)).collect();
+            assert_eq!(frames.len(), 4);
+            assert_eq!(frames[1].timestamp - frames[0].timestamp, 500);
+            assert_eq!(frames[0].low as u32, bench.low_beam.get());
+            assert_eq!(frames[0].battery_mv, 8400);
+            assert_eq!(frames[0].temp, 20);
+
+            assert_eq!(bench.send("telemetry 0\n"), "ok\n");
+            assert_eq!(bench.send(""), "");
+        });
+    }
+
+    #[test]
+    fn reset_is_delegated() {
+        with_console(&|bench| {
+            assert_eq!(bench.resets.get(), 0);
+            bench.send("reset\n");
+            assert_eq!(bench.resets.get(), 1);
+        });
+    }
+
+    struct Bench<'a> {
+        send: &'a dyn Fn(&[u8]) -> Vec<u8>,
+        low_beam: &'a Cell<u32>,
+        high_beam: &'a Cell<u32>,
+        resets: &'a Cell<u32>,
+    }
+
+    impl Bench<'_> {
+        /// Sends the input and returns the output after animations are done
+        fn send(&self, input: &str) -> String {
+            String::from_utf8(self.send_raw(input)).unwrap()
+        }
+
+        fn send_raw(&self, input: &str) -> Vec<u8> {
+            (self.send)(input.as_bytes())
+        }
+    }
+
+    fn with_console(block: &dyn Fn(Bench)) {
+        let idle = Cell::new(false);
+        let led = TestLed::default();
+        let led_high = TestLed::default();
+        let rgb = TestRgb::default();
+        let sensors = TestSensors::default();
+        let resets = Cell::new(0);
+        let storage = MemoryStorage::create();
+        let serial = TestSerial::default();
+        let reset = || resets.set(resets.get() + 1);
+        let console = Console::new(&serial, &reset);
+        let edt = EDT::create();
+        let light_control = LightControl::new(
+            TestPin { down: &idle },
+            TestPin { down: &idle },
+            TestPin { down: &idle },
+            &led,
+            &led_high,
+            &rgb,
+            &edt,
+            &sensors,
+            &storage,
+            COMMUTE,
+        );
+        light_control.start();
+        light_control.jump_start();
+        let advance_time = |time: u32| {
+            edt.advance_time_by(time, &|msg| light_control.process_message(msg));
+        };
+        advance_time(2000);
+
+        let send = |input: &[u8]| {
+            console.receive(input, &light_control);
+            advance_time(2000);
+            serial.output.take()
+        };
+        block(Bench {
+            send: &send,
+            low_beam: &led.output,
+            high_beam: &led_high.output,
+            resets: &resets,
+        });
+        assert_eq!(rgb.get_rgb() & BLUE != 0, light_control.high_beam());
+    }
+
+    #[derive(Default)]
+    struct TestSerial {
+        output: RefCell<Vec<u8>>,
+    }
+
+    impl Serial for TestSerial {
+        fn write(&self, data: &[u8]) {
+            self.output.borrow_mut().extend_from_slice(data);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::SensorError;
+    use light_control::bsp::ambient::Lux;
+    use light_control::dipping::{DippingConfig, GlareDetector};
+
+    use crate::common::{with_bench, Bench, Setup};
+
+    /// Dark road with oncoming cars at 10 s and 20 s and a lit village from 30 s to 40 s
+    const TRAFFIC: &str = include_str!("data/oncoming_traffic.csv");
+
+    #[test]
+    fn oncoming_light_is_detected_until_the_delay() {
+        let detector = GlareDetector::new(DippingConfig::default());
+        for _ in 0..20 {
+            assert!(!detector.update(Ok(Lux(1)), 50));
+        }
+        assert!(!detector.update(Ok(Lux(2)), 50));
+        assert!(detector.update(Ok(Lux(3)), 50));
+        assert!(detector.update(Ok(Lux(1)), 1950));
+        assert!(!detector.update(Ok(Lux(1)), 50));
+        assert!(!detector.update(Err(SensorError::Adc), 50));
+    }
+
+    #[test]
+    fn background_follows_slow_changes() {
+        let detector = GlareDetector::new(DippingConfig::default());
+        // dusk or the own beam on a light road
+        for lux in 4..20 {
+            assert!(!detector.update(Ok(Lux(lux)), 1000));
+        }
+        assert!(detector.background().is_some_and(|it| it.0 >= 12));
+        assert!(!detector.update(Ok(Lux(25)), 50));
+        assert!(detector.update(Ok(Lux(40)), 50));
+    }
+
+    #[test]
+    fn auto_dipping_needs_a_sensor() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(!bench.light_control.set_auto_dipping(true));
+        });
+    }
+
+    #[test]
+    fn high_beam_is_dropped_for_oncoming_traffic() {
+        with_bench(oncoming_traffic(), &|bench| {
+            assert!(high_beam_at(&bench, 9900));
+            assert!(!high_beam_at(&bench, 10500));
+            assert!(bench.light_control.is_dipped());
+            assert!(!high_beam_at(&bench, 13500));
+            assert!(high_beam_at(&bench, 14500));
+            assert!(!bench.light_control.is_dipped());
+
+            assert!(!high_beam_at(&bench, 20500));
+            assert!(high_beam_at(&bench, 23500));
+            // streetlights dip it for the whole village
+            assert!(!high_beam_at(&bench, 35000));
+            assert!(!high_beam_at(&bench, 41500));
+            assert!(high_beam_at(&bench, 42500));
+        });
+    }
+
+    #[test]
+    fn rider_overrides_the_dipping() {
+        with_bench(oncoming_traffic(), &|bench| {
+            assert!(!high_beam_at(&bench, 10500));
+            bench.click(bench.toggle);
+            assert!(bench.light_control.high_beam());
+            assert!(high_beam_at(&bench, 13000));
+            assert!(high_beam_at(&bench, 14500));
+            // until the next car
+            assert!(!high_beam_at(&bench, 20500));
+
+            bench.click(bench.toggle);
+            bench.click(bench.toggle);
+            assert!(!bench.light_control.high_beam());
+            assert!(!high_beam_at(&bench, 25000));
+        });
+    }
+
+    #[test]
+    fn turning_it_off_restores_the_high_beam() {
+        with_bench(oncoming_traffic(), &|bench| {
+            assert!(!high_beam_at(&bench, 10500));
+            assert!(bench.light_control.set_auto_dipping(false));
+            assert!(bench.light_control.high_beam());
+            assert!(high_beam_at(&bench, 20500));
+        });
+    }
+
+    /// Rides with the high beam and auto dipping on, as recorded in [TRAFFIC]
+    fn oncoming_traffic() -> Setup<'static> {
+        Setup {
+            forward_light: Some(TRAFFIC),
+            configure: Some(&|light_control| {
+                assert!(light_control.set_auto_dipping(true));
+                light_control.set_high_beam(true);
+            }),
+            ..Setup::default()
+        }
+    }
+
+    /// Advances to [time] in ms since the start of [TRAFFIC]
+    fn high_beam_at(bench: &Bench, time: u32) -> bool {
+        bench.advance_time(time - bench.edt.now());
+        bench.light_control.high_beam()
+    }
+}
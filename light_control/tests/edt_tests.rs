@@ -2,27 +2,20 @@
 mod tests {
     use no_std_compat::cell::RefCell;
 
-    use light_control::edt::EDT;
+    use light_control::edt::{is_before, Event, QueueFull, Stats, EDT};
 
     #[test]
     fn events_are_appended_to_the_queue() {
         let edt: EDT<u32> = EDT::create();
 
         // when
-        edt.schedule(1000, 1);
-        edt.schedule(3000, 2);
-        edt.schedule(3000, 3);
-        edt.schedule(3000, 4);
+        edt.schedule(1000, 1).unwrap();
+        edt.schedule(3000, 2).unwrap();
+        edt.schedule(3000, 3).unwrap();
+        edt.schedule(3000, 4).unwrap();
 
         // assert that queue has 4 messages
-        assert_eq!(
-            edt.queue
-                .borrow()
-                .iter()
-                .filter(|it| { it.is_some() })
-                .count(),
-            4
-        );
+        assert_eq!(edt.len(), 4);
     }
 
     #[test]
@@ -30,26 +23,25 @@ mod tests {
         let edt: EDT<u32> = EDT::create();
 
         // given
-        edt.schedule(1000, 1);
-        edt.schedule(3000, 2);
-        edt.schedule(3000, 2);
-        edt.schedule(3000, 4);
+        edt.schedule(1000, 1).unwrap();
+        edt.schedule(3000, 2).unwrap();
+        edt.schedule(3000, 2).unwrap();
+        edt.schedule(3000, 4).unwrap();
 
         // when
         edt.remove(|payload| *payload == 2);
 
         // then 2 messages are removed, 2 retained
-        let retained = edt.queue.borrow().iter().filter(|it| it.is_some()).count();
-        assert_eq!(retained, 2);
+        assert_eq!(edt.len(), 2);
     }
 
     #[test]
     fn events_are_handled_based_on_the_time() {
         let edt: EDT<u32> = EDT::create();
-        edt.schedule(30, 3);
-        edt.schedule(20, 2);
-        edt.schedule(10, 1);
-        edt.schedule(0, 0);
+        edt.schedule(30, 3).unwrap();
+        edt.schedule(20, 2).unwrap();
+        edt.schedule(10, 1).unwrap();
+        edt.schedule(0, 0).unwrap();
 
         let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
 
@@ -64,32 +56,25 @@ mod tests {
         assert_eq!(events.borrow_mut().remove(0), 2);
 
         // then one event message remains
-        assert_eq!(
-            edt.queue
-                .borrow()
-                .iter()
-                .filter(|it| { it.is_some() })
-                .count(),
-            1
-        );
+        assert_eq!(edt.len(), 1);
     }
 
     #[test]
     fn events_are_handled_based_on_the_insertion_order() {
         let edt: EDT<u32> = EDT::create();
         // these 4 occupy first 4 elements in the array
-        edt.schedule(10, 0);
-        edt.schedule(10, 1);
-        edt.schedule(10, 2);
-        edt.schedule(5, 3);
+        edt.schedule(10, 0).unwrap();
+        edt.schedule(10, 1).unwrap();
+        edt.schedule(10, 2).unwrap();
+        edt.schedule(5, 3).unwrap();
 
         // this creates a "hole" in the array
         edt.remove(|payload| *payload == 1);
         edt.remove(|payload| *payload == 2);
 
         // events are scheduled in the "hole"
-        edt.schedule(10, 4);
-        edt.schedule(10, 5);
+        edt.schedule(10, 4).unwrap();
+        edt.schedule(10, 5).unwrap();
 
         let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
 
@@ -104,7 +89,186 @@ mod tests {
         assert_eq!(events.borrow_mut().remove(0), 4); // this was due in 10, order 1
         assert_eq!(events.borrow_mut().remove(0), 5); // this was due in 10, order 2
 
-        // then one event message remains
-        assert_eq!(edt.queue.borrow().iter().all(|it| { it.is_none() }), true);
+        // then no messages remain
+        assert!(edt.is_empty());
+    }
+
+    #[test]
+    fn events_with_same_time_are_handled_in_fifo_order() {
+        let edt: EDT<u32> = EDT::create();
+        for i in 0..16 {
+            edt.schedule(10, i).unwrap();
+        }
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+
+        assert_eq!(*events.borrow(), (0..16).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn events_are_handled_in_time_order_regardless_of_scheduling_order() {
+        let edt: EDT<u32, 64> = EDT::create();
+        let delays: Vec<u32> = (0..64).map(|i| (i * 37) % 64).collect();
+        for &delay in &delays {
+            edt.schedule(delay, delay).unwrap();
+        }
+        edt.remove(|payload| payload % 2 == 1);
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(100, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+
+        assert_eq!(*events.borrow(), (0..64).step_by(2).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn schedule_fails_when_queue_is_full() {
+        let edt: EDT<u32, 2> = EDT::create();
+        assert!(edt.schedule(10, 1).is_ok());
+        assert!(edt.schedule(10, 2).is_ok());
+        assert_eq!(edt.schedule(10, 3), Err(QueueFull));
+        assert_eq!(edt.stats().overflows, 1);
+        assert_eq!(edt.len(), 2);
+    }
+
+    #[test]
+    fn stats_report_high_water_mark() {
+        let edt: EDT<u32, 8> = EDT::create();
+        for i in 0..5 {
+            edt.schedule(i, i).unwrap();
+        }
+        edt.advance_time_by(10, &|_| {});
+        edt.schedule(10, 6).unwrap();
+
+        assert_eq!(
+            edt.stats(),
+            Stats {
+                len: 1,
+                capacity: 8,
+                high_water_mark: 5,
+                overflows: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn events_are_handled_in_order_across_time_wrap() {
+        let edt: EDT<u32> = EDT::create_at(u32::MAX - 15);
+        edt.schedule(30, 3).unwrap();
+        edt.schedule(10, 1).unwrap();
+        edt.schedule(20, 2).unwrap();
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![1, 2]);
+        assert_eq!(edt.now(), 9);
+
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn wait_is_calculated_across_time_wrap() {
+        let edt: EDT<u32> = EDT::create_at(u32::MAX - 4);
+        edt.schedule(10, 1).unwrap();
+
+        match edt.poll() {
+            Event::Wait { ms } => assert_eq!(ms, 10),
+            _ => panic!("expected to wait"),
+        }
+        assert_eq!(edt.now(), 5);
+        match edt.poll() {
+            Event::Execute { msg } => assert_eq!(msg, 1),
+            _ => panic!("expected to execute"),
+        }
+    }
+
+    #[test]
+    fn timestamps_are_compared_across_time_wrap() {
+        assert!(is_before(u32::MAX, 0));
+        assert!(!is_before(0, u32::MAX));
+        assert!(is_before(1, 2));
+        assert!(!is_before(2, 2));
+    }
+
+    #[test]
+    fn cancelled_event_is_not_handled() {
+        let edt: EDT<u32> = EDT::create();
+        let first = edt.schedule(10, 1).unwrap();
+        let second = edt.schedule(10, 1).unwrap();
+
+        assert!(edt.cancel(first));
+        assert!(!edt.cancel(first));
+        assert!(!edt.is_scheduled(first));
+        assert!(edt.is_scheduled(second));
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![1]);
+        assert!(!edt.cancel(second));
+    }
+
+    #[test]
+    fn rescheduled_event_is_handled_later() {
+        let edt: EDT<u32> = EDT::create();
+        let handle = edt.schedule(10, 1).unwrap();
+        edt.schedule(20, 2).unwrap();
+
+        assert!(edt.reschedule(handle, 30));
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![2]);
+        edt.advance_time_by(25, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![2, 1]);
+        assert!(!edt.reschedule(handle, 30));
+    }
+
+    #[test]
+    fn periodic_event_is_handled_until_cancelled() {
+        let edt: EDT<u32> = EDT::create();
+        let handle = edt.schedule_every(10, 1).unwrap();
+        edt.schedule(35, 2).unwrap();
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(45, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![1, 1, 1, 2, 1]);
+
+        assert!(edt.cancel(handle));
+        edt.advance_time_by(45, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        assert_eq!(*events.borrow(), vec![1, 1, 1, 2, 1]);
+        assert!(edt.is_empty());
+    }
+
+    #[test]
+    fn periodic_event_can_be_rescheduled() {
+        let edt: EDT<u32> = EDT::create();
+        let handle = edt.schedule_every(10, 1).unwrap();
+        assert!(edt.reschedule(handle, 25));
+
+        let events: RefCell<Vec<u32>> = RefCell::new(vec![]);
+        edt.advance_time_by(40, &|payload| {
+            events.borrow_mut().push(payload);
+        });
+        // at 25 and 35
+        assert_eq!(*events.borrow(), vec![1, 1]);
     }
 }
@@ -0,0 +1,117 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::future::{poll_fn, Future};
+    use std::sync::Mutex;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use embassy_time::{Duration, MockDriver};
+
+    use light_control::bsp::rgb::RED;
+    use light_control::control::{Action, LightControl, BUTTON_CHECK_PERIOD};
+    use light_control::edt::EDT;
+    use light_control::embassy_runner::run;
+    use light_control::gestures::MULTI_CLICK_WINDOW;
+    use light_control::profile::COMMUTE;
+    use light_control::record_store::MemoryStorage;
+
+    use crate::common::{TestLed, TestPin, TestRgb, TestSensors};
+
+    /// Mock time driver is global, tests must not advance it concurrently
+    static TIME: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn timers_are_driven_by_embassy_time() {
+        with_runner(&|advance_time, plus_pin, _input, low_beam, _rgb| {
+            advance_time(2000);
+            assert_eq!(low_beam.get(), COMMUTE.low[3] as u32);
+
+            plus_pin.set(true);
+            advance_time(BUTTON_CHECK_PERIOD * 2);
+            plus_pin.set(false);
+            advance_time(MULTI_CLICK_WINDOW + 1000);
+            assert_eq!(low_beam.get(), COMMUTE.low[4] as u32);
+        });
+    }
+
+    #[test]
+    fn input_is_handled_immediately() {
+        with_runner(&|advance_time, _plus_pin, input, _low_beam, rgb| {
+            advance_time(2000);
+            rgb.set(0);
+
+            input.set(Some(Action::Blink {
+                color: RED,
+                blinks: 2,
+                period: 100,
+            }));
+            advance_time(1);
+            assert_eq!(rgb.get(), RED);
+            advance_time(100);
+            assert_eq!(rgb.get(), 0);
+        });
+    }
+
+    type Block<'a> =
+        &'a dyn Fn(&dyn Fn(u32), &Cell<bool>, &Cell<Option<Action>>, &Cell<u32>, &Cell<u8>);
+
+    fn with_runner(block: Block) {
+        let _lock = TIME.lock().unwrap_or_else(|e| e.into_inner());
+        let plus_pin = Cell::new(false);
+        let idle = Cell::new(false);
+        let input: Cell<Option<Action>> = Cell::new(None);
+        let low_led = TestLed::default();
+        let high_led = TestLed::default();
+        let rgb = TestRgb::default();
+        let sensors = TestSensors::default();
+        let storage = MemoryStorage::create();
+        let edt = EDT::create();
+        let light_control = LightControl::new(
+            TestPin { down: &plus_pin },
+            TestPin { down: &idle },
+            TestPin { down: &idle },
+            &low_led,
+            &high_led,
+            &rgb,
+            &edt,
+            &sensors,
+            &storage,
+            COMMUTE,
+        );
+        light_control.start();
+        light_control.jump_start();
+
+        let runner = run(&light_control, &edt, || {
+            poll_fn(|_| match input.take() {
+                Some(action) => Poll::Ready(action),
+                None => Poll::Pending,
+            })
+        });
+        let runner = RefCell::new(Box::pin(runner));
+        let waker = noop_waker();
+
+        // polls the runner every millisecond
+        let advance_time = |time: u32| {
+            let mut runner = runner.borrow_mut();
+            let mut cx = Context::from_waker(&waker);
+            for _ in 0..time {
+                let _ = runner.as_mut().poll(&mut cx);
+                MockDriver::get().advance(Duration::from_millis(1));
+            }
+            let _ = runner.as_mut().poll(&mut cx);
+        };
+
+        block(&advance_time, &plus_pin, &input, &low_led.output, &rgb.rgb);
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use light_control::battery::BatteryModel;
+    use light_control::fuel_gauge::{FuelGauge, FuelGaugeConfig};
+    use light_control::perceived_light_math::current_ma;
+
+    const PERIOD: u32 = 500;
+
+    #[test]
+    fn charge_starts_from_voltage() {
+        let gauge = FuelGauge::new(FuelGaugeConfig::default());
+        assert_eq!(gauge.remaining_mah(), 0);
+        gauge.update(0, &BatteryModel::default(), 7700, 0);
+        assert_eq!(gauge.capacity(), 70);
+        assert_eq!(gauge.remaining_mah(), 2380);
+    }
+
+    #[test]
+    fn load_is_compensated_on_start() {
+        let gauge = FuelGauge::new(FuelGaugeConfig::default());
+        let current = current_ma(7700, 1000, 100);
+        gauge.update(0, &BatteryModel::default(), 7700, current);
+        assert_eq!(gauge.current_ma(), current);
+        assert_eq!(
+            gauge.open_circuit_voltage(7700, current),
+            7700 + current * 320 / 1000
+        );
+        assert!(gauge.capacity() > 70);
+    }
+
+    #[test]
+    fn current_is_integrated() {
+        let gauge = FuelGauge::new(FuelGaugeConfig::default());
+        let model = BatteryModel::default();
+        gauge.update(0, &model, 7700, 0);
+        let mut now = 0;
+        while now < 3_600_000 {
+            now += PERIOD;
+            gauge.update(now, &model, 7700, current_ma(7700, 1000, 100));
+        }
+        assert_eq!(gauge.remaining_mah(), 2380 - current_ma(7700, 1000, 100));
+    }
+
+    #[test]
+    fn resistance_is_learned_from_load_changes() {
+        let gauge = FuelGauge::new(FuelGaugeConfig::default());
+        let model = BatteryModel::default();
+        let expected = 200 * 1000 / current_ma(7800, 1000, 100);
+        for i in 0..40 {
+            let on = i % 2 == 1;
+            let voltage = if on { 7800 } else { 8000 };
+            let current = if on {
+                current_ma(voltage, 1000, 100)
+            } else {
+                0
+            };
+            gauge.update(i * PERIOD, &model, voltage, current);
+        }
+        assert!(
+            gauge.resistance().abs_diff(expected) <= 4,
+            "{} != {}",
+            gauge.resistance(),
+            expected
+        );
+    }
+
+    #[test]
+    fn charge_is_recalibrated_after_rest() {
+        let config = FuelGaugeConfig::default();
+        let gauge = FuelGauge::new(config);
+        let model = BatteryModel::default();
+        gauge.update(0, &model, 8400, 0);
+        // counting drifted, the battery is emptier than it thinks
+        let mut now = 0;
+        while now < 600_000 {
+            now += PERIOD;
+            gauge.update(now, &model, 7700, current_ma(7700, 1000, 50));
+        }
+        let counted = gauge.capacity();
+        assert!(counted > 70);
+
+        let rest_start = now;
+        while now < rest_start + config.rest_time - PERIOD {
+            now += PERIOD;
+            gauge.update(now, &model, 7700, 0);
+        }
+        assert_eq!(gauge.capacity(), counted);
+        now += PERIOD;
+        gauge.update(now, &model, 7700, 0);
+        assert_eq!(gauge.capacity(), 70);
+    }
+}
@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use light_control::gestures::{
+        Button, Gesture, GestureConfig, GestureRecognizer, HOLD_REPEAT_PERIOD,
+        LONG_CLICK_THRESHOLD, MINUS, MULTI_CLICK_WINDOW, PLUS,
+    };
+
+    const PERIOD: u32 = 50;
+
+    #[test]
+    fn short_press_is_a_click_after_multi_click_window() {
+        let bench = Bench::new();
+        bench.hold([true, false, false], 100);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW - PERIOD);
+        assert_eq!(bench.take(), vec![]);
+        bench.hold([false, false, false], PERIOD);
+        assert_eq!(bench.take(), vec![Gesture::Click(Button::Plus)]);
+    }
+
+    #[test]
+    fn two_clicks_are_a_double_click() {
+        let bench = Bench::new();
+        bench.click([false, true, false]);
+        bench.click([false, true, false]);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        assert_eq!(bench.take(), vec![Gesture::DoubleClick(Button::Minus)]);
+    }
+
+    #[test]
+    fn three_clicks_are_a_triple_click_without_waiting() {
+        let bench = Bench::new();
+        bench.click([false, false, true]);
+        bench.click([false, false, true]);
+        bench.hold([false, false, true], PERIOD);
+        bench.hold([false, false, false], PERIOD);
+        assert_eq!(bench.take(), vec![Gesture::TripleClick(Button::Toggle)]);
+    }
+
+    #[test]
+    fn clicks_far_apart_are_separate_clicks() {
+        let bench = Bench::new();
+        bench.click([true, false, false]);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        bench.click([true, false, false]);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        assert_eq!(
+            bench.take(),
+            vec![Gesture::Click(Button::Plus), Gesture::Click(Button::Plus)]
+        );
+    }
+
+    #[test]
+    fn hold_is_a_long_click_followed_by_repeats_and_release() {
+        let bench = Bench::new();
+        bench.hold([true, false, false], LONG_CLICK_THRESHOLD);
+        assert_eq!(bench.take(), vec![Gesture::LongClick(Button::Plus)]);
+        bench.hold([true, false, false], HOLD_REPEAT_PERIOD * 2);
+        assert_eq!(
+            bench.take(),
+            vec![
+                Gesture::HoldRepeat(Button::Plus),
+                Gesture::HoldRepeat(Button::Plus)
+            ]
+        );
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        assert_eq!(bench.take(), vec![Gesture::Released(Button::Plus)]);
+    }
+
+    #[test]
+    fn click_followed_by_hold_is_click_and_hold() {
+        let bench = Bench::new();
+        bench.click([false, true, false]);
+        bench.hold([false, true, false], LONG_CLICK_THRESHOLD);
+        bench.hold([false, false, false], PERIOD);
+        assert_eq!(
+            bench.take(),
+            vec![
+                Gesture::ClickAndHold(Button::Minus),
+                Gesture::Released(Button::Minus)
+            ]
+        );
+    }
+
+    #[test]
+    fn pressing_two_buttons_is_a_chord() {
+        let bench = Bench::new();
+        bench.hold([true, false, false], PERIOD);
+        bench.hold([true, true, false], LONG_CLICK_THRESHOLD * 2);
+        bench.hold([false, true, false], PERIOD);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        assert_eq!(
+            bench.take(),
+            vec![Gesture::Chord {
+                buttons: PLUS | MINUS
+            }]
+        );
+    }
+
+    #[test]
+    fn buttons_work_individually_after_chord() {
+        let bench = Bench::new();
+        bench.hold([true, true, false], PERIOD);
+        bench.hold([false, false, false], PERIOD);
+        bench.click([true, false, false]);
+        bench.hold([false, false, false], MULTI_CLICK_WINDOW);
+        assert_eq!(
+            bench.take(),
+            vec![
+                Gesture::Chord {
+                    buttons: PLUS | MINUS
+                },
+                Gesture::Click(Button::Plus)
+            ]
+        );
+    }
+
+    #[test]
+    fn config_changes_timings() {
+        let bench = Bench::new();
+        bench.recognizer.set_config(GestureConfig {
+            long_click_threshold: 200,
+            ..GestureConfig::default()
+        });
+        bench.hold([true, false, false], 200);
+        assert_eq!(bench.take(), vec![Gesture::LongClick(Button::Plus)]);
+    }
+
+    struct Bench {
+        recognizer: GestureRecognizer,
+        gestures: RefCell<Vec<Gesture>>,
+    }
+
+    impl Bench {
+        fn new() -> Self {
+            Bench {
+                recognizer: GestureRecognizer::new(GestureConfig::default()),
+                gestures: RefCell::new(vec![]),
+            }
+        }
+
+        /// Samples the pins every [PERIOD] for the given time
+        fn hold(&self, pins_down: [bool; 3], time: u32) {
+            for _ in 0..time / PERIOD {
+                self.recognizer.update(pins_down, PERIOD, &mut |gesture| {
+                    self.gestures.borrow_mut().push(gesture)
+                });
+            }
+        }
+
+        fn click(&self, pins_down: [bool; 3]) {
+            self.hold(pins_down, PERIOD);
+            self.hold([false, false, false], PERIOD);
+        }
+
+        fn take(&self) -> Vec<Gesture> {
+            self.gestures.replace(vec![])
+        }
+    }
+}
@@ -1,22 +1,29 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
-    use light_control::bsp::adc::Sensors;
+    use light_control::bsp::adc::{Decidegrees, Millivolts, SensorError, Sensors};
     use std::cell::Cell;
     use std::mem::size_of_val;
 
     use light_control::bsp::led::{Led, MAX};
     use light_control::bsp::pin::Pin;
     use light_control::bsp::rgb::Rgb;
+    use light_control::bsp::storage::Storage;
     use light_control::control::{
-        Action, LightControl, ANIM_DURATION, BUTTON_CHECK_PERIOD, MAX_POWER_LEVEL,
-        POWER_LEVELS_HIGH, POWER_LEVELS_LOW, POWER_LEVELS_LOW_AUX,
+        Action, LightControl, ANIM_DURATION, BUTTON_CHECK_PERIOD, SAVE_SETTINGS_DELAY,
     };
     use light_control::edt::EDT;
+    use light_control::gestures::MULTI_CLICK_WINDOW;
+    use light_control::profile::{Profile, COMMUTE, STVZO, TRAIL};
+    use light_control::record_store::MemoryStorage;
+
+    use crate::common::{self, Setup};
 
     #[test]
     fn edt_queue_size_is_below_1kb() {
         let edt = EDT::<Action>::create();
-        assert!(size_of_val(&edt.queue) < 500);
+        assert!(size_of_val(&edt) < 500);
     }
 
     #[test]
@@ -42,7 +49,7 @@ mod tests {
             for _ in 0..3 {
                 buttons.click_plus();
             }
-            assert_eq!(low_beam.get(), low(MAX_POWER_LEVEL));
+            assert_eq!(low_beam.get(), low(COMMUTE.max_level()));
         });
     }
 
@@ -83,19 +90,19 @@ mod tests {
             buttons.press_minus();
             advance_time(700);
             buttons.release_minus();
-            advance_time(100 + ANIM_DURATION);
+            advance_time(100 + MULTI_CLICK_WINDOW + ANIM_DURATION);
             assert_eq!(low_beam.get(), low(2));
 
             buttons.press_plus();
             advance_time(700);
             buttons.release_plus();
-            advance_time(100 + ANIM_DURATION);
+            advance_time(100 + MULTI_CLICK_WINDOW + ANIM_DURATION);
             assert_eq!(low_beam.get(), low(3));
         });
     }
 
     #[test]
-    fn long_clicks_are_nop() {
+    fn long_clicks_without_sensors_are_nop() {
         with_bench(&|advance_time, buttons, low_beam, _high_beam| {
             // startup animation
             advance_time(2000);
@@ -107,6 +114,46 @@ mod tests {
         });
     }
 
+    #[test]
+    fn long_clicks_switch_the_sensor_modes() {
+        let setup = Setup {
+            ambient_light: true,
+            wheel: true,
+            forward_light: Some("0,0\n"),
+            ..Setup::default()
+        };
+        common::with_bench(setup, &|bench| {
+            bench.long_click(bench.toggle);
+            assert!(bench.light_control.auto());
+            bench.long_click(bench.plus);
+            assert!(bench.light_control.speed_mode());
+            assert!(!bench.light_control.auto());
+            bench.long_click(bench.minus);
+            assert!(bench.light_control.auto_dipping());
+        });
+    }
+
+    #[test]
+    fn plus_double_click_jumps_to_max_brightness() {
+        with_bench(&|advance_time, buttons, low_beam, _high_beam| {
+            // startup animation
+            advance_time(2000);
+            buttons.click_minus();
+            buttons.double_click_plus();
+            assert_eq!(low_beam.get(), low(COMMUTE.max_level()));
+        });
+    }
+
+    #[test]
+    fn minus_double_click_jumps_to_min_brightness() {
+        with_bench(&|advance_time, buttons, low_beam, _high_beam| {
+            // startup animation
+            advance_time(2000);
+            buttons.double_click_minus();
+            assert_eq!(low_beam.get(), low(1));
+        });
+    }
+
     #[test]
     fn toggle_click_switches_on_high_beam() {
         with_bench(&|advance_time, buttons, low_beam, high_beam| {
@@ -114,7 +161,7 @@ mod tests {
             advance_time(2000);
             assert_eq!(low_beam.get(), low(3));
             buttons.click_toggle();
-            assert_eq!(low_beam.get(), low_aux(3) * 100 / 140);
+            assert_eq!(low_beam.get(), low_aux(3) * COMMUTE.low_aux_ratio / 100);
             assert_eq!(high_beam.get(), high(3));
         });
     }
@@ -126,7 +173,7 @@ mod tests {
             advance_time(2000);
             buttons.click_toggle();
             buttons.click_plus();
-            assert_eq!(low_beam.get(), low_aux(4) * 100 / 140);
+            assert_eq!(low_beam.get(), low_aux(4) * COMMUTE.low_aux_ratio / 100);
             assert_eq!(high_beam.get(), high(4));
         });
     }
@@ -141,39 +188,128 @@ mod tests {
             buttons.click_minus();
             buttons.click_minus();
             buttons.click_minus();
-            assert_eq!(low_beam.get(), low_aux(1) * 100 / 140);
+            assert_eq!(low_beam.get(), low_aux(1) * COMMUTE.low_aux_ratio / 100);
             assert_eq!(high_beam.get(), high(1));
         });
     }
 
+    #[test]
+    fn toggle_triple_click_switches_profile() {
+        with_bench(&|advance_time, buttons, low_beam, high_beam| {
+            // startup animation
+            advance_time(2000);
+            buttons.triple_click_toggle();
+            assert_eq!(low_beam.get(), profile_low(&TRAIL, 3));
+            assert_eq!(high_beam.get(), 0);
+            buttons.triple_click_toggle();
+            assert_eq!(low_beam.get(), profile_low(&STVZO, 3));
+            buttons.triple_click_toggle();
+            assert_eq!(low_beam.get(), low(3));
+        });
+    }
+
+    #[test]
+    fn clicks_work_across_time_wrap() {
+        let storage = MemoryStorage::create();
+        with_bench_at(
+            u32::MAX - 3000,
+            &storage,
+            &|advance_time, buttons, low_beam, _high_beam| {
+                // startup animation
+                advance_time(2000);
+                for _ in 0..4 {
+                    buttons.click_minus();
+                    buttons.click_plus();
+                }
+                assert_eq!(low_beam.get(), low(3));
+                buttons.click_plus();
+                assert_eq!(low_beam.get(), low(4));
+            },
+        );
+    }
+
+    #[test]
+    fn last_mode_is_restored_after_restart() {
+        let storage = MemoryStorage::create();
+        with_bench_and_storage(&storage, &|advance_time, buttons, _low_beam, _high_beam| {
+            advance_time(2000);
+            buttons.click_plus();
+            buttons.click_toggle();
+            advance_time(SAVE_SETTINGS_DELAY);
+        });
+
+        with_bench_and_storage(&storage, &|advance_time, _buttons, low_beam, high_beam| {
+            // startup animation
+            advance_time(2000);
+            assert_eq!(low_beam.get(), low_aux(4) * COMMUTE.low_aux_ratio / 100);
+            assert_eq!(high_beam.get(), high(4));
+        });
+    }
+
+    #[test]
+    fn last_profile_is_restored_after_restart() {
+        let storage = MemoryStorage::create();
+        with_bench_and_storage(&storage, &|advance_time, buttons, _low_beam, _high_beam| {
+            advance_time(2000);
+            buttons.triple_click_toggle();
+            advance_time(SAVE_SETTINGS_DELAY);
+        });
+
+        with_bench_and_storage(&storage, &|advance_time, _buttons, low_beam, _high_beam| {
+            // startup animation
+            advance_time(2000);
+            assert_eq!(low_beam.get(), profile_low(&TRAIL, 3));
+        });
+    }
+
+    fn profile_low(profile: &Profile, i: usize) -> u32 {
+        profile.low[i] as u32
+    }
+
     fn low(i: usize) -> u32 {
         assert!(
-            i < POWER_LEVELS_LOW.len(),
+            i < COMMUTE.levels(),
             "Test level exceeds available levels: {}",
             i
         );
-        POWER_LEVELS_LOW[i] as u32
+        COMMUTE.low[i] as u32
     }
 
     fn low_aux(i: usize) -> u32 {
         assert!(
-            i < POWER_LEVELS_LOW_AUX.len(),
+            i < COMMUTE.levels(),
             "Test level exceeds available levels: {}",
             i
         );
-        POWER_LEVELS_LOW_AUX[i] as u32
+        COMMUTE.low_aux[i] as u32
     }
 
     fn high(i: usize) -> u32 {
         assert!(
-            i < POWER_LEVELS_HIGH.len(),
+            i < COMMUTE.levels(),
             "Test level exceeds available levels: {}",
             i
         );
-        POWER_LEVELS_HIGH[i] as u32
+        COMMUTE.high[i] as u32
     }
 
     fn with_bench(block: &dyn Fn(&dyn Fn(u32), Buttons, &Cell<u32>, &Cell<u32>)) {
+        with_bench_and_storage(&MemoryStorage::create(), block);
+    }
+
+    fn with_bench_and_storage(
+        storage: &dyn Storage,
+        block: &dyn Fn(&dyn Fn(u32), Buttons, &Cell<u32>, &Cell<u32>),
+    ) {
+        with_bench_at(0, storage, block);
+    }
+
+    /// Bench which starts at the given time
+    fn with_bench_at(
+        now: u32,
+        storage: &dyn Storage,
+        block: &dyn Fn(&dyn Fn(u32), Buttons, &Cell<u32>, &Cell<u32>),
+    ) {
         let plus_pin = Cell::new(false);
         let minus_pin = Cell::new(false);
         let toggle_pin = Cell::new(false);
@@ -186,7 +322,7 @@ mod tests {
             power_output: &high_beam,
         };
         let rgb = TestRgb { rgb: Cell::new(0) };
-        let edt = EDT::create();
+        let edt = EDT::create_at(now);
         let light_control = LightControl::new(
             TestPin { is_down: &plus_pin },
             TestPin {
@@ -200,6 +336,8 @@ mod tests {
             &rgb,
             &edt,
             &TestSensors {},
+            storage,
+            COMMUTE,
         );
         light_control.start();
         light_control.jump_start();
@@ -287,6 +425,20 @@ mod tests {
             (self.advance_time)(BUTTON_CHECK_PERIOD);
             self.release_plus();
             (self.advance_time)(BUTTON_CHECK_PERIOD);
+            (self.advance_time)(MULTI_CLICK_WINDOW);
+            (self.advance_time)(ANIM_DURATION);
+        }
+        fn double_click_plus(&self) {
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.press_plus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.release_plus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.press_plus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.release_plus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            (self.advance_time)(MULTI_CLICK_WINDOW);
             (self.advance_time)(ANIM_DURATION);
         }
         fn long_click_plus(&self) {
@@ -309,6 +461,20 @@ mod tests {
             (self.advance_time)(BUTTON_CHECK_PERIOD);
             self.release_minus();
             (self.advance_time)(BUTTON_CHECK_PERIOD);
+            (self.advance_time)(MULTI_CLICK_WINDOW);
+            (self.advance_time)(ANIM_DURATION);
+        }
+        fn double_click_minus(&self) {
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.press_minus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.release_minus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.press_minus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            self.release_minus();
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
+            (self.advance_time)(MULTI_CLICK_WINDOW);
             (self.advance_time)(ANIM_DURATION);
         }
         fn long_click_minus(&self) {
@@ -331,6 +497,17 @@ mod tests {
             (self.advance_time)(BUTTON_CHECK_PERIOD);
             self.release_toggle();
             (self.advance_time)(BUTTON_CHECK_PERIOD);
+            (self.advance_time)(MULTI_CLICK_WINDOW);
+            (self.advance_time)(ANIM_DURATION);
+        }
+        fn triple_click_toggle(&self) {
+            for _ in 0..3 {
+                (self.advance_time)(BUTTON_CHECK_PERIOD);
+                self.press_toggle();
+                (self.advance_time)(BUTTON_CHECK_PERIOD);
+                self.release_toggle();
+            }
+            (self.advance_time)(BUTTON_CHECK_PERIOD);
             (self.advance_time)(ANIM_DURATION);
         }
         fn long_click_toggle(&self) {
@@ -360,12 +537,12 @@ mod tests {
     pub struct TestSensors {}
 
     impl Sensors for TestSensors {
-        fn battery_voltage(&self, _high_percentage: u32, _low_percentage: u32) -> u32 {
-            8400
+        fn battery_voltage(&self) -> Result<Millivolts, SensorError> {
+            Ok(Millivolts(8400))
         }
 
-        fn temp(&self) -> i32 {
-            20
+        fn temp(&self) -> Result<Decidegrees, SensorError> {
+            Ok(Decidegrees::from_celsius(20))
         }
     }
 }
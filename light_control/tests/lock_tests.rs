@@ -0,0 +1,76 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::rgb::{GREEN, RED};
+    use light_control::control::ANIM_DURATION;
+    use light_control::gestures::LONG_CLICK_THRESHOLD;
+    use light_control::pattern::Pattern;
+    use light_control::profile::COMMUTE;
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn click_and_hold_locks_and_unlocks() {
+        with_bench(Setup::default(), &|bench| {
+            bench.light_control.set_high_beam(true);
+            bench.click_and_hold(bench.toggle);
+            assert!(bench.light_control.is_locked());
+            bench.advance_time(ANIM_DURATION);
+            assert_eq!((bench.low_beam.get(), bench.high_beam.get()), (0, 0));
+
+            bench.click_and_hold(bench.toggle);
+            assert!(!bench.light_control.is_locked());
+            bench.advance_time(ANIM_DURATION);
+            assert_eq!(bench.light_control.power_level(), 3);
+            assert!(bench.light_control.high_beam());
+        });
+    }
+
+    #[test]
+    fn clicks_are_ignored_while_locked() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.lock());
+            assert!(!bench.light_control.lock());
+            for pin in [bench.plus, bench.minus, bench.toggle] {
+                bench.click(pin);
+                assert_eq!(bench.rgb.get() & (RED | GREEN), RED | GREEN);
+                bench.advance_time(1000);
+                assert_eq!(bench.low_beam.get(), 0);
+            }
+            assert!(!bench.light_control.set_power_level(1));
+            assert!(!bench.light_control.start_pattern(Pattern::Sos));
+            bench.light_control.set_high_beam(true);
+            assert!(!bench.light_control.high_beam());
+
+            assert!(bench.light_control.unlock());
+            bench.advance_time(ANIM_DURATION);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[3] as u32);
+        });
+    }
+
+    #[test]
+    fn holding_a_button_peeks() {
+        with_bench(Setup::default(), &|bench| {
+            bench.light_control.lock();
+            bench.plus.set(true);
+            bench.advance_time(LONG_CLICK_THRESHOLD + ANIM_DURATION + 100);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert!(bench.light_control.is_locked());
+            bench.plus.set(false);
+            bench.advance_time(ANIM_DURATION + 100);
+            assert_eq!(bench.low_beam.get(), 0);
+        });
+    }
+
+    #[test]
+    fn locking_stops_the_pattern() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.start_pattern(Pattern::Beacon));
+            bench.click_and_hold(bench.toggle);
+            assert_eq!(bench.light_control.pattern(), None);
+            bench.advance_time(3000);
+            assert_eq!(bench.low_beam.get(), 0);
+        });
+    }
+}
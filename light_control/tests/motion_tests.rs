@@ -0,0 +1,133 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use light_control::bsp::adc::SensorError;
+    use light_control::bsp::motion::{Acceleration, Accelerometer};
+    use light_control::control::BUTTON_CHECK_PERIOD;
+    use light_control::motion::{MotionConfig, MotionDetector, MotionState};
+    use light_control::pattern::Pattern;
+    use light_control::profile::COMMUTE;
+    use sensor_replay::CsvAccelerometer;
+
+    use crate::common::{with_bench, Setup};
+
+    const RIDE: &str = include_str!("data/parked_ride.csv");
+
+    const CONFIG: MotionConfig = MotionConfig {
+        threshold: 60,
+        dim_after: 3000,
+        off_after: 8000,
+        parking_level: 1,
+    };
+
+    const STILL: Acceleration = Acceleration {
+        x: 0,
+        y: 0,
+        z: 1000,
+    };
+
+    #[test]
+    fn stationary_time_dims_and_switches_off() {
+        let detector = MotionDetector::new(CONFIG);
+        for _ in 0..59 {
+            assert_eq!(detector.update(Ok(STILL), 50), MotionState::Moving);
+        }
+        assert_eq!(detector.update(Ok(STILL), 50), MotionState::Parked);
+        assert_eq!(detector.update(Ok(STILL), 5000), MotionState::Off);
+        let bump = Acceleration { z: 1200, ..STILL };
+        assert_eq!(detector.update(Ok(bump), 50), MotionState::Moving);
+    }
+
+    #[test]
+    fn broken_sensor_counts_as_movement() {
+        let detector = MotionDetector::new(CONFIG);
+        detector.update(Ok(STILL), 5000);
+        assert_eq!(detector.state(), MotionState::Parked);
+        assert_eq!(
+            detector.update(Err(SensorError::Adc), 50),
+            MotionState::Moving
+        );
+    }
+
+    #[test]
+    fn recording_is_replayed() {
+        let now = Cell::new(0);
+        let clock = || now.get();
+        let accelerometer = CsvAccelerometer::create("time,x,y,z\n100,1,2,3\n200,4,5,6\n", &clock);
+        assert_eq!(accelerometer.acceleration(), Err(SensorError::Adc));
+        now.set(150);
+        assert_eq!(
+            accelerometer.acceleration(),
+            Ok(Acceleration { x: 1, y: 2, z: 3 })
+        );
+        now.set(1000);
+        assert_eq!(accelerometer.acceleration().map(|it| it.z), Ok(6));
+    }
+
+    #[test]
+    fn parked_ride_dims_and_wakes_up() {
+        with_bench(parked_ride(), &|bench| {
+            let riding = COMMUTE.low_aux[3] as u32 * COMMUTE.low_aux_ratio / 100;
+            assert_eq!(bench.low_beam.get(), riding);
+            bench.advance_time(5000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Moving);
+
+            bench.advance_time(3000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Parked);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert_eq!(bench.high_beam.get(), 0);
+
+            bench.advance_time(5000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Off);
+            assert_eq!(bench.low_beam.get(), 0);
+
+            // riding again at 16 s
+            bench.advance_time(4000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Moving);
+            assert!(bench.light_control.high_beam());
+            assert_eq!(bench.low_beam.get(), riding);
+        });
+    }
+
+    #[test]
+    fn button_wakes_up_without_changing_the_level() {
+        with_bench(parked_ride(), &|bench| {
+            bench.advance_time(13000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Off);
+            bench.plus.set(true);
+            bench.advance_time(2 * BUTTON_CHECK_PERIOD);
+            bench.plus.set(false);
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.power_level(), 3);
+            assert!(bench.light_control.high_beam());
+        });
+    }
+
+    #[test]
+    fn parking_stops_the_pattern() {
+        with_bench(parked_ride(), &|bench| {
+            assert!(bench.light_control.start_pattern(Pattern::Beacon));
+            bench.advance_time(8000);
+            assert_eq!(bench.light_control.motion_state(), MotionState::Parked);
+            assert_eq!(bench.light_control.pattern(), None);
+            bench.advance_time(1000);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert_eq!(bench.high_beam.get(), 0);
+        });
+    }
+
+    /// Rides on level 3 with the high beam on, as recorded in [RIDE]
+    fn parked_ride() -> Setup<'static> {
+        Setup {
+            motion: Some(RIDE),
+            configure: Some(&|light_control| {
+                light_control.set_motion_config(CONFIG);
+                light_control.set_high_beam(true);
+            }),
+            ..Setup::default()
+        }
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::{Decidegrees, Millivolts, SensorError};
+    use light_control::ntc::{Ntc, NtcEquation, NtcPlacement, NtcTable, NTC_10K_B3435};
+    use light_control::voltage_to_temp::voltage_to_temp;
+
+    /// Datasheet example coefficients of a 10 kΩ thermistor
+    const STEINHART_HART: Ntc = Ntc {
+        equation: NtcEquation::SteinhartHart {
+            a: 1_009_249_522,
+            b: 237_840_544,
+            c: 201_920,
+        },
+        ..NTC_10K_B3435
+    };
+
+    const TABLE: NtcTable<33> = NTC_10K_B3435.lookup_table(Millivolts(300), Millivolts(3000));
+
+    #[test]
+    fn nominal_resistance_is_nominal_temperature() {
+        assert_eq!(NTC_10K_B3435.resistance(Millivolts(1650)), Ok(10_000));
+        assert_eq!(NTC_10K_B3435.temp(Millivolts(1650)), Ok(Decidegrees(250)));
+    }
+
+    #[test]
+    fn beta_equation_matches_reference_values() {
+        // R = 10k * exp(3435 * (1/T - 1/298.15)), divider voltage computed with f64
+        let expected = [(-200, 2923), (0, 2447), (500, 960), (850, 418), (1200, 192)];
+        for (temp, mv) in expected {
+            let actual = NTC_10K_B3435.temp(Millivolts(mv)).unwrap().0;
+            assert!(
+                (actual - temp).abs() <= 10,
+                "{} mV: {} != {}",
+                mv,
+                actual,
+                temp
+            );
+        }
+    }
+
+    #[test]
+    fn steinhart_hart_equation() {
+        // 1/T = A + B ln(10k) + C ln(10k)^3 = 1/297.8 K
+        assert_eq!(STEINHART_HART.temp(Millivolts(1650)), Ok(Decidegrees(247)));
+    }
+
+    #[test]
+    fn piecewise_fit_is_reproduced() {
+        for mv in (450..2300).step_by(50) {
+            let fitted = voltage_to_temp(mv);
+            let actual = NTC_10K_B3435.temp(Millivolts(mv)).unwrap().celsius();
+            assert!(
+                (actual - fitted).abs() <= 2,
+                "{} mV: {} != {}",
+                mv,
+                actual,
+                fitted
+            );
+        }
+    }
+
+    #[test]
+    fn open_and_shorted_thermistors_are_detected() {
+        assert_eq!(
+            NTC_10K_B3435.temp(Millivolts(3300)),
+            Err(SensorError::Disconnected)
+        );
+        assert_eq!(
+            NTC_10K_B3435.temp(Millivolts(3290)),
+            Err(SensorError::Disconnected)
+        );
+        assert_eq!(
+            NTC_10K_B3435.temp(Millivolts(0)),
+            Err(SensorError::ShortCircuit)
+        );
+
+        let high_side = Ntc {
+            placement: NtcPlacement::HighSide,
+            ..NTC_10K_B3435
+        };
+        assert_eq!(high_side.temp(Millivolts(1650)), Ok(Decidegrees(250)));
+        assert!(high_side.temp(Millivolts(2000)).unwrap() > Decidegrees(250));
+        assert_eq!(
+            high_side.temp(Millivolts(0)),
+            Err(SensorError::Disconnected)
+        );
+        assert_eq!(
+            high_side.temp(Millivolts(3300)),
+            Err(SensorError::ShortCircuit)
+        );
+    }
+
+    #[test]
+    fn lookup_table_interpolates_the_equation() {
+        for mv in (300..=3000).step_by(10) {
+            let exact = NTC_10K_B3435.temp(Millivolts(mv)).unwrap().0;
+            let interpolated = TABLE.temp(Millivolts(mv)).unwrap().0;
+            assert!(
+                (exact - interpolated).abs() <= 10,
+                "{} mV: {} != {}",
+                mv,
+                interpolated,
+                exact
+            );
+        }
+        assert_eq!(TABLE.temp(Millivolts(3100)), Err(SensorError::Disconnected));
+        assert_eq!(TABLE.temp(Millivolts(200)), Err(SensorError::ShortCircuit));
+    }
+}
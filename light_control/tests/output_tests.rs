@@ -0,0 +1,99 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::led::Led;
+    use light_control::output::{
+        ChannelConfig, Levels, Mode, Role, DAYTIME_RUNNING, HIGH_BEAM, LOW_BEAM, REAR, TRAILER,
+    };
+    use light_control::profile::{COMMUTE, TRAIL};
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn beams_follow_the_profile() {
+        assert_eq!(LOW_BEAM.output(&TRAIL, Mode::LowBeam, 2, 100, 100), 50);
+        assert_eq!(LOW_BEAM.output(&COMMUTE, Mode::HighBeam, 3, 100, 100), 46);
+        assert_eq!(HIGH_BEAM.output(&TRAIL, Mode::LowBeam, 2, 100, 100), 0);
+        assert_eq!(HIGH_BEAM.output(&TRAIL, Mode::HighBeam, 2, 50, 100), 40);
+    }
+
+    #[test]
+    fn own_levels_are_clamped_to_the_table() {
+        let config = ChannelConfig {
+            low_beam: Levels::Table(&[0, 20]),
+            ..REAR
+        };
+        assert_eq!(config.output(&TRAIL, Mode::LowBeam, 0, 100, 100), 0);
+        assert_eq!(config.output(&TRAIL, Mode::LowBeam, 4, 100, 100), 20);
+        assert_eq!(TRAILER.output(&TRAIL, Mode::HighBeam, 0, 100, 100), 0);
+        assert_eq!(TRAILER.output(&TRAIL, Mode::HighBeam, 1, 100, 100), 100);
+    }
+
+    #[test]
+    fn heat_is_weighted() {
+        assert_eq!(REAR.output(&TRAIL, Mode::LowBeam, 4, 100, 0), 100);
+        assert_eq!(
+            DAYTIME_RUNNING.output(&TRAIL, Mode::LowBeam, 4, 100, 40),
+            70
+        );
+        assert_eq!(LOW_BEAM.output(&TRAIL, Mode::LowBeam, 4, 100, 40), 40);
+        // battery throttle applies to all channels
+        assert_eq!(REAR.output(&TRAIL, Mode::LowBeam, 4, 0, 100), 0);
+    }
+
+    #[test]
+    fn added_channels_follow_level_and_mode() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.add_channel(bench.rear, REAR));
+            assert!(bench.light_control.add_channel(bench.drl, DAYTIME_RUNNING));
+            bench.advance_time(2000);
+            assert_eq!(bench.rear.get(), 80);
+            assert_eq!(bench.drl.get(), 100);
+
+            bench.light_control.set_power_level(1);
+            bench.light_control.set_high_beam(true);
+            bench.advance_time(2000);
+            assert_eq!(bench.rear.get(), 40);
+            assert_eq!(bench.drl.get(), 0);
+
+            assert!(bench.light_control.set_channel_config(ChannelConfig {
+                high_beam: Levels::Fixed(30),
+                ..DAYTIME_RUNNING
+            }));
+            bench.advance_time(2000);
+            assert_eq!(bench.drl.get(), 30);
+            assert!(!bench.light_control.set_channel_config(TRAILER));
+            assert_eq!(bench.light_control.channel_config(Role::Rear), Some(REAR));
+        });
+    }
+
+    #[test]
+    fn channels_are_limited() {
+        with_bench(Setup::default(), &|bench| {
+            for _ in 0..4 {
+                assert!(bench.light_control.add_channel(bench.rear, TRAILER));
+            }
+            assert!(!bench.light_control.add_channel(bench.rear, TRAILER));
+            let roles: Vec<Role> = bench
+                .light_control
+                .outputs()
+                .iter()
+                .flatten()
+                .map(|it| it.0)
+                .collect();
+            assert_eq!(&roles[..3], &[Role::LowBeam, Role::HighBeam, Role::Trailer]);
+        });
+    }
+
+    #[test]
+    fn added_channels_draw_current() {
+        with_bench(Setup::default(), &|bench| {
+            bench.advance_time(2000);
+            let without = bench.light_control.battery_current().0;
+            bench.light_control.add_channel(bench.rear, TRAILER);
+            bench.advance_time(2000);
+            assert!(bench.light_control.battery_current().0 > without);
+        });
+    }
+}
@@ -0,0 +1,173 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::Millivolts;
+    use light_control::control::ANIM_DURATION;
+    use light_control::output::Role;
+    use light_control::pattern::{Pattern, Step, StrobeConfig, Track, PATTERNS};
+    use light_control::profile::{Profile, COMMUTE, STVZO};
+    use light_control::protection::ProtectionState;
+
+    use crate::common::{with_bench, Setup};
+
+    const FLASH: &[Track] = &[Track {
+        role: Role::HighBeam,
+        steps: &[
+            Step {
+                output: 100,
+                duration: 300,
+            },
+            Step {
+                output: 0,
+                duration: 300,
+            },
+        ],
+    }];
+
+    #[test]
+    fn steps_loop_per_role() {
+        let sos = Pattern::Sos;
+        assert_eq!(sos.len(Role::LowBeam), 18);
+        assert_eq!(sos.step(Role::HighBeam, 18), sos.step(Role::HighBeam, 0));
+        assert_eq!(sos.step(Role::DaytimeRunning, 0), None);
+        let total: u32 = (0..18)
+            .filter_map(|i| sos.step(Role::LowBeam, i))
+            .map(|it| it.duration as u32)
+            .sum();
+        assert_eq!(total, 34 * 200);
+
+        let strobe = Pattern::Strobe(StrobeConfig {
+            on: 20,
+            off: 180,
+            output: 80,
+        });
+        assert!(strobe.is_valid());
+        assert_eq!(
+            strobe.step(Role::LowBeam, 3),
+            Some(Step {
+                output: 0,
+                duration: 180
+            })
+        );
+        assert_eq!(strobe.len(Role::HighBeam), 0);
+
+        let flash = Pattern::Custom {
+            tracks: FLASH,
+            looping: false,
+        };
+        assert_eq!(flash.step(Role::HighBeam, 1).map(|it| it.output), Some(0));
+        assert_eq!(flash.step(Role::HighBeam, 2), None);
+        assert_eq!(Pattern::by_name("beacon"), Some(Pattern::Beacon));
+        assert_eq!(Pattern::by_name("disco"), None);
+    }
+
+    #[test]
+    fn triple_click_plays_sos() {
+        with_bench(Setup::default(), &|bench| {
+            bench.triple_click(bench.plus);
+            assert_eq!(bench.light_control.pattern(), Some(Pattern::Sos));
+            bench.advance_time(100);
+            assert_eq!((bench.low_beam.get(), bench.high_beam.get()), (100, 100));
+            bench.advance_time(200);
+            assert_eq!((bench.low_beam.get(), bench.high_beam.get()), (0, 0));
+            // dash after the first letter
+            bench.advance_time(1500);
+            assert_eq!(bench.low_beam.get(), 100);
+            bench.advance_time(500);
+            assert_eq!(bench.low_beam.get(), 0);
+        });
+    }
+
+    #[test]
+    fn triple_clicks_cycle_back_to_the_steady_level() {
+        with_bench(Setup::default(), &|bench| {
+            for pattern in PATTERNS {
+                bench.triple_click(bench.plus);
+                assert_eq!(bench.light_control.pattern(), Some(*pattern));
+            }
+            bench.triple_click(bench.plus);
+            assert_eq!(bench.light_control.pattern(), None);
+            bench.advance_time(ANIM_DURATION + 100);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[3] as u32);
+        });
+    }
+
+    #[test]
+    fn click_cancels_the_pattern() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.start_pattern(Pattern::Beacon));
+            bench.advance_time(1000);
+            assert_eq!(bench.low_beam.get(), 0);
+            bench.click(bench.plus);
+            assert_eq!(bench.light_control.pattern(), None);
+            bench.advance_time(ANIM_DURATION + 100);
+            // the click only cancelled
+            assert_eq!(bench.light_control.power_level(), 3);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[3] as u32);
+        });
+    }
+
+    #[test]
+    fn custom_pattern_ends_on_its_own() {
+        with_bench(Setup::default(), &|bench| {
+            let flash = Pattern::Custom {
+                tracks: FLASH,
+                looping: false,
+            };
+            assert!(bench.light_control.start_pattern(flash));
+            assert_eq!((bench.low_beam.get(), bench.high_beam.get()), (0, 100));
+            bench.advance_time(400);
+            assert_eq!(bench.high_beam.get(), 0);
+            bench.advance_time(300);
+            assert_eq!(bench.light_control.pattern(), None);
+            bench.advance_time(ANIM_DURATION);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[3] as u32);
+        });
+    }
+
+    #[test]
+    fn stvzo_does_not_allow_flashing() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.start_pattern(Pattern::Sos));
+            bench.light_control.set_profile(STVZO);
+            assert_eq!(bench.light_control.pattern(), None);
+            assert!(!bench.light_control.start_pattern(Pattern::Sos));
+        });
+    }
+
+    #[test]
+    fn flashing_follows_the_profile_not_its_name() {
+        with_bench(Setup::default(), &|bench| {
+            bench.light_control.set_profile(Profile {
+                name: "city",
+                ..STVZO
+            });
+            assert!(!bench.light_control.start_pattern(Pattern::Sos));
+
+            bench.light_control.set_profile(Profile {
+                allows_flashing: true,
+                ..STVZO
+            });
+            assert!(bench.light_control.start_pattern(Pattern::Sos));
+        });
+    }
+
+    #[test]
+    fn low_battery_stops_and_refuses_patterns() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(bench.light_control.start_pattern(Pattern::Beacon));
+            bench.voltage.set(Ok(Millivolts(6600)));
+            bench.advance_time(2000);
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::LowMode
+            );
+            assert_eq!(bench.light_control.pattern(), None);
+            assert!(!bench.light_control.start_pattern(Pattern::Sos));
+            bench.advance_time(3000);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert_eq!(bench.high_beam.get(), 0);
+        });
+    }
+}
@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use light_control::profile::{Profile, PROFILES, STVZO};
+
+    #[test]
+    fn predefined_profiles_are_valid() {
+        for profile in PROFILES {
+            assert!(profile.is_valid(), "{} is invalid", profile.name);
+        }
+    }
+
+    #[test]
+    fn profiles_are_found_by_name() {
+        assert_eq!(Profile::by_name("stvzo"), Some(STVZO));
+        assert_eq!(Profile::by_name("disco"), None);
+    }
+
+    #[test]
+    fn profile_with_mismatching_tables_is_invalid() {
+        let profile = Profile {
+            high: &[0, 50, 100],
+            ..STVZO
+        };
+        assert!(!profile.is_valid());
+    }
+}
@@ -0,0 +1,138 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::Millivolts;
+    use light_control::bsp::rgb::RED;
+    use light_control::control::BUTTON_CHECK_PERIOD;
+    use light_control::profile::COMMUTE;
+    use light_control::protection::{BatteryProtection, ProtectionConfig, ProtectionState};
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn states_follow_capacity() {
+        let protection = BatteryProtection::new(ProtectionConfig::default());
+        assert_eq!(protection.update(50, 500), ProtectionState::Normal);
+        assert_eq!(protection.update(10, 500), ProtectionState::Warning);
+        assert_eq!(protection.update(5, 500), ProtectionState::LowMode);
+        assert_eq!(protection.update(2, 500), ProtectionState::Shutdown);
+    }
+
+    #[test]
+    fn recovering_capacity_needs_hysteresis() {
+        let protection = BatteryProtection::new(ProtectionConfig::default());
+        protection.update(5, 500);
+        assert_eq!(protection.update(8, 500), ProtectionState::LowMode);
+        assert_eq!(protection.update(10, 500), ProtectionState::LowMode);
+        assert_eq!(protection.update(11, 500), ProtectionState::Warning);
+        assert_eq!(protection.update(15, 500), ProtectionState::Warning);
+        assert_eq!(protection.update(16, 500), ProtectionState::Normal);
+    }
+
+    #[test]
+    fn shutdown_is_latched_until_overridden() {
+        let config = ProtectionConfig::default();
+        let protection = BatteryProtection::new(config);
+        assert!(!protection.override_lockout());
+        protection.update(0, 500);
+        assert_eq!(protection.update(100, 500), ProtectionState::Shutdown);
+
+        assert!(protection.override_lockout());
+        assert_eq!(protection.state(), ProtectionState::LowMode);
+        assert_eq!(
+            protection.update(0, config.reserve_time - 1),
+            ProtectionState::LowMode
+        );
+        assert_eq!(protection.update(0, 1), ProtectionState::Shutdown);
+    }
+
+    #[test]
+    fn warning_blinks_red_and_keeps_the_level() {
+        with_bench(Setup::default(), &|bench| {
+            bench.voltage.set(Ok(Millivolts(7050)));
+            let mut blinks = 0;
+            for _ in 0..40 {
+                let red = bench.rgb.get() & RED != 0;
+                bench.advance_time(50);
+                if !red && bench.rgb.get() & RED != 0 {
+                    blinks += 1;
+                }
+            }
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::Warning
+            );
+            assert_eq!(blinks, 3);
+            assert_eq!(bench.light_control.power_level(), 3);
+        });
+    }
+
+    #[test]
+    fn low_battery_forces_lowest_level_and_then_shuts_down() {
+        with_bench(Setup::default(), &|bench| {
+            bench.light_control.set_high_beam(true);
+            bench.advance_time(2000);
+            assert!(bench.high_beam.get() > 0);
+
+            bench.voltage.set(Ok(Millivolts(6600)));
+            bench.advance_time(2000);
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::LowMode
+            );
+            assert_eq!(bench.light_control.power_level(), 1);
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+            assert_eq!(bench.high_beam.get(), 0);
+            assert!(!bench.light_control.set_power_level(3));
+
+            // voltage recovers without the load, not enough to leave the low mode
+            bench.voltage.set(Ok(Millivolts(7000)));
+            bench.advance_time(2000);
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::LowMode
+            );
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+
+            bench.voltage.set(Ok(Millivolts(6200)));
+            bench.advance_time(2000);
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::Shutdown
+            );
+            assert_eq!(bench.low_beam.get(), 0);
+        });
+    }
+
+    #[test]
+    fn lockout_is_overridden_by_plus_and_minus() {
+        with_bench(Setup::default(), &|bench| {
+            bench.voltage.set(Ok(Millivolts(6000)));
+            bench.advance_time(2000);
+            assert_eq!(bench.low_beam.get(), 0);
+
+            // a fresh battery does not unlock
+            bench.voltage.set(Ok(Millivolts(8400)));
+            bench.advance_time(2000);
+            bench.plus.set(true);
+            bench.advance_time(2 * BUTTON_CHECK_PERIOD);
+            bench.plus.set(false);
+            bench.advance_time(2000);
+            assert_eq!(bench.low_beam.get(), 0);
+
+            bench.voltage.set(Ok(Millivolts(6000)));
+            bench.plus.set(true);
+            bench.minus.set(true);
+            bench.advance_time(2 * BUTTON_CHECK_PERIOD);
+            bench.plus.set(false);
+            bench.minus.set(false);
+            bench.advance_time(2000);
+            assert_eq!(
+                bench.light_control.protection_state(),
+                ProtectionState::LowMode
+            );
+            assert_eq!(bench.low_beam.get(), COMMUTE.low[1] as u32);
+        });
+    }
+}
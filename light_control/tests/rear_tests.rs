@@ -0,0 +1,137 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use light_control::bsp::adc::{Millivolts, SensorError};
+    use light_control::bsp::brake::BrakeInput;
+    use light_control::bsp::led::Led;
+    use light_control::bsp::motion::{Acceleration, Accelerometer};
+    use light_control::control::BUTTON_CHECK_PERIOD;
+    use light_control::output::{Mode, REAR};
+    use light_control::profile::STVZO;
+    use light_control::rear::{DecelerationBrake, RearConfig, RearLight, RearMode};
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn brake_light_is_held() {
+        let config = RearConfig::default();
+        let rear = RearLight::new(config);
+        rear.update(50, true, true);
+        assert_eq!(rear.output(40, 100, true), 100);
+        assert_eq!(rear.output(40, 50, true), 50);
+        rear.update(config.brake_hold - 1, false, true);
+        assert!(rear.is_braking());
+        rear.update(1, false, true);
+        assert_eq!(rear.output(40, 100, true), 40);
+
+        // off resets the brake light
+        rear.update(50, true, true);
+        rear.update(50, false, false);
+        assert!(!rear.is_braking());
+        assert_eq!(rear.output(0, 100, true), 0);
+    }
+
+    #[test]
+    fn pulse_flashes_if_allowed() {
+        let config = RearConfig {
+            mode: RearMode::Pulse,
+            ..RearConfig::default()
+        };
+        let rear = RearLight::new(config);
+        assert_eq!(rear.output(40, 100, true), 100);
+        assert_eq!(rear.output(40, 100, false), 40);
+        rear.update(config.pulse_on, false, true);
+        assert_eq!(rear.output(40, 100, true), 40);
+        rear.update(config.pulse_period - config.pulse_on, false, true);
+        assert_eq!(rear.output(40, 100, true), 100);
+    }
+
+    #[test]
+    fn deceleration_is_filtered() {
+        let x = Cell::new(0);
+        let accelerometer = TestAccelerometer { x: &x };
+        let brake = DecelerationBrake::new(&accelerometer, 200);
+        x.set(-600);
+        assert!(!brake.is_braking());
+        x.set(0);
+        assert!(!brake.is_braking());
+        x.set(-400);
+        assert!((0..5).any(|_| brake.is_braking()));
+    }
+
+    #[test]
+    fn rear_channel_brakes_and_follows_the_front() {
+        with_bench(rear_and_brake(), &|bench| {
+            assert_eq!(bench.rear.get(), 80);
+            bench.braking.set(true);
+            bench.advance_time(BUTTON_CHECK_PERIOD);
+            assert_eq!(bench.rear.get(), 100);
+            assert!(bench.light_control.is_braking());
+            bench.braking.set(false);
+            bench.advance_time(2000);
+            assert_eq!(bench.rear.get(), 80);
+
+            bench.light_control.set_power_level(1);
+            bench.advance_time(BUTTON_CHECK_PERIOD);
+            assert_eq!(bench.rear.get(), 40);
+
+            // battery lockout switches the rear light off too, brake or not
+            bench.voltage.set(Ok(Millivolts(6000)));
+            bench.braking.set(true);
+            bench.advance_time(2000);
+            assert_eq!(bench.low_beam.get(), 0);
+            assert_eq!(bench.rear.get(), 0);
+        });
+    }
+
+    #[test]
+    fn stvzo_profile_suppresses_pulse() {
+        with_bench(rear_and_brake(), &|bench| {
+            bench.light_control.set_rear_config(RearConfig {
+                mode: RearMode::Pulse,
+                ..RearConfig::default()
+            });
+            let max = || {
+                (0..20)
+                    .map(|_| {
+                        bench.advance_time(BUTTON_CHECK_PERIOD);
+                        bench.rear.get()
+                    })
+                    .max()
+                    .unwrap()
+            };
+            assert_eq!(max(), 100);
+            bench.light_control.set_profile(STVZO);
+            bench.advance_time(BUTTON_CHECK_PERIOD);
+            assert_eq!(
+                max(),
+                REAR.output(&STVZO, Mode::LowBeam, 3, 100, 100) as u32
+            );
+        });
+    }
+
+    fn rear_and_brake() -> Setup<'static> {
+        Setup {
+            rear: true,
+            brake: true,
+            ..Setup::default()
+        }
+    }
+
+    struct TestAccelerometer<'a> {
+        x: &'a Cell<i32>,
+    }
+
+    impl Accelerometer for TestAccelerometer<'_> {
+        fn acceleration(&self) -> Result<Acceleration, SensorError> {
+            Ok(Acceleration {
+                x: self.x.get(),
+                y: 0,
+                z: 1000,
+            })
+        }
+    }
+}
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::storage::{FlashPages, Storage, StorageError};
+    use light_control::record_store::{
+        crc16, MemoryFlash, MemoryStorage, RecordStore, MAX_VALUE_SIZE,
+    };
+
+    #[test]
+    fn empty_storage_has_no_values() {
+        let storage = MemoryStorage::create();
+        assert_eq!(storage.read(1, &mut [0; 4]), None);
+    }
+
+    #[test]
+    fn latest_value_is_read() {
+        let storage = MemoryStorage::create();
+        storage.write(1, &[1, 2]).unwrap();
+        storage.write(2, &[7]).unwrap();
+        storage.write(1, &[3, 4, 5]).unwrap();
+
+        let mut buf = [0; 4];
+        assert_eq!(storage.read(1, &mut buf), Some(3));
+        assert_eq!(&buf[..3], &[3, 4, 5]);
+        assert_eq!(storage.read(2, &mut buf), Some(1));
+        assert_eq!(buf[0], 7);
+    }
+
+    #[test]
+    fn values_survive_remount() {
+        let flash: MemoryFlash<256, 2> = MemoryFlash::create();
+        RecordStore::new(&flash).write(1, &[42]).unwrap();
+
+        let mut buf = [0; 1];
+        assert_eq!(RecordStore::new(&flash).read(1, &mut buf), Some(1));
+        assert_eq!(buf[0], 42);
+    }
+
+    #[test]
+    fn unchanged_values_are_not_written_again() {
+        let flash: MemoryFlash<256, 2> = MemoryFlash::create();
+        let storage = RecordStore::new(&flash);
+        for _ in 0..1000 {
+            storage.write(1, &[42]).unwrap();
+        }
+        assert_eq!(flash.erase_count(0) + flash.erase_count(1), 1);
+    }
+
+    #[test]
+    fn compaction_keeps_latest_values_and_spreads_erases() {
+        let flash: MemoryFlash<256, 2> = MemoryFlash::create();
+        let storage = RecordStore::new(&flash);
+        storage.write(100, &[1, 2, 3]).unwrap();
+        for i in 0..1000u32 {
+            storage.write(1, &i.to_le_bytes()).unwrap();
+            storage.write(2, &[i as u8]).unwrap();
+        }
+
+        let mut buf = [0; 4];
+        assert_eq!(storage.read(1, &mut buf), Some(4));
+        assert_eq!(u32::from_le_bytes(buf), 999);
+        assert_eq!(storage.read(2, &mut buf), Some(1));
+        assert_eq!(buf[0], 999u32 as u8);
+        assert_eq!(storage.read(100, &mut buf), Some(3));
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+
+        let diff = flash.erase_count(0) as i32 - flash.erase_count(1) as i32;
+        assert!(diff.abs() <= 1, "erases are not balanced: {}", diff);
+    }
+
+    #[test]
+    fn corrupted_record_is_ignored() {
+        let flash: MemoryFlash<256, 2> = MemoryFlash::create();
+        RecordStore::new(&flash).write(1, &[1]).unwrap();
+        // torn write of the second record: header is there, value is not
+        let crc = crc16(crc16(0xffff, &[1, 1]), &[2]).to_le_bytes();
+        flash
+            .write(0, 16, &[1, 1, crc[0], crc[1], 0xff, 0xff, 0xff, 0xff])
+            .unwrap();
+
+        let storage = RecordStore::new(&flash);
+        let mut buf = [0; 1];
+        assert_eq!(storage.read(1, &mut buf), Some(1));
+        assert_eq!(buf[0], 1);
+        // and the storage is still writable
+        storage.write(1, &[3]).unwrap();
+        assert_eq!(RecordStore::new(&flash).read(1, &mut buf), Some(1));
+        assert_eq!(buf[0], 3);
+    }
+
+    #[test]
+    fn interrupted_compaction_keeps_previous_page() {
+        let flash: MemoryFlash<256, 2> = MemoryFlash::create();
+        RecordStore::new(&flash).write(1, &[1]).unwrap();
+        // next page contains records, but no header
+        flash.erase(1).unwrap();
+        flash.write(1, 8, &[0; 8]).unwrap();
+
+        let mut buf = [0; 1];
+        assert_eq!(RecordStore::new(&flash).read(1, &mut buf), Some(1));
+        assert_eq!(buf[0], 1);
+    }
+
+    #[test]
+    fn invalid_records_are_rejected() {
+        let storage = MemoryStorage::create();
+        assert_eq!(storage.write(0xff, &[1]), Err(StorageError::InvalidRecord));
+        assert_eq!(
+            storage.write(1, &[0; MAX_VALUE_SIZE + 1]),
+            Err(StorageError::InvalidRecord)
+        );
+    }
+}
@@ -0,0 +1,21 @@
+#[cfg(test)]
+mod tests {
+    use light_control::runtime::{runtime_blinks, runtime_minutes, MAX_RUNTIME_BLINKS};
+
+    #[test]
+    fn runtime_is_remaining_charge_by_current() {
+        assert_eq!(runtime_minutes(3400, 1700), Some(120));
+        assert_eq!(runtime_minutes(100, 1000), Some(6));
+        assert_eq!(runtime_minutes(0, 1000), Some(0));
+        assert_eq!(runtime_minutes(3400, 0), None);
+    }
+
+    #[test]
+    fn blinks_are_counted_per_started_half_hour() {
+        assert_eq!(runtime_blinks(0), 1);
+        assert_eq!(runtime_blinks(30), 1);
+        assert_eq!(runtime_blinks(31), 2);
+        assert_eq!(runtime_blinks(150), 5);
+        assert_eq!(runtime_blinks(10_000), MAX_RUNTIME_BLINKS);
+    }
+}
@@ -0,0 +1,63 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::{Decidegrees, SensorError};
+    use light_control::bsp::rgb::{BLUE, RED};
+    use light_control::control::SENSOR_FAULT_THROTTLE;
+    use light_control::profile::COMMUTE;
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn decidegrees_are_rounded_down_to_celsius() {
+        assert_eq!(Decidegrees(215).celsius(), 21);
+        assert_eq!(Decidegrees(-5).celsius(), -1);
+        assert_eq!(Decidegrees::from_celsius(-20), Decidegrees(-200));
+    }
+
+    #[test]
+    fn disconnected_ntc_limits_output_and_blinks() {
+        with_bench(Setup::default(), &|bench| {
+            assert_eq!(bench.light_control.throttle(), 100);
+            assert_eq!(bench.light_control.sensor_fault(), None);
+
+            bench.temp.set(Err(SensorError::Disconnected));
+            bench.advance_time(1000);
+            assert_eq!(
+                bench.light_control.sensor_fault(),
+                Some(SensorError::Disconnected)
+            );
+            assert_eq!(bench.light_control.throttle(), SENSOR_FAULT_THROTTLE);
+            assert_eq!(
+                bench.low_beam.get(),
+                COMMUTE.low[3] as u32 * SENSOR_FAULT_THROTTLE / 100
+            );
+            // purple blinks
+            let colors = (0..20)
+                .map(|_| {
+                    bench.advance_time(50);
+                    bench.rgb.get()
+                })
+                .filter(|&rgb| rgb == RED | BLUE)
+                .count();
+            assert!(colors > 0);
+
+            bench.temp.set(Ok(Decidegrees::from_celsius(25)));
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.sensor_fault(), None);
+            assert_eq!(bench.light_control.throttle(), 100);
+        });
+    }
+
+    #[test]
+    fn failed_battery_read_keeps_the_light_on() {
+        with_bench(Setup::default(), &|bench| {
+            bench.voltage.set(Err(SensorError::Adc));
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.sensor_fault(), Some(SensorError::Adc));
+            assert_eq!(bench.light_control.throttle(), SENSOR_FAULT_THROTTLE);
+            assert_eq!(bench.light_control.telemetry().battery_mv, 0);
+        });
+    }
+}
@@ -0,0 +1,117 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::speed::{SpeedConfig, SpeedEstimator};
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn speed_is_estimated_from_pulses() {
+        let estimator = SpeedEstimator::new(SpeedConfig::default());
+        // 2.1 m every 500 ms is 15 km/h
+        for i in 0..=8 {
+            estimator.update(i * 500, i);
+        }
+        assert_eq!(estimator.speed(), 15);
+        // an overdue pulse slows it down, the timeout stops it
+        estimator.update(4000 + 2000, 8);
+        assert_eq!(estimator.speed(), 3);
+        estimator.update(4000 + 3000, 8);
+        assert_eq!(estimator.speed(), 0);
+    }
+
+    #[test]
+    fn huge_pulse_jump_does_not_overflow() {
+        let estimator = SpeedEstimator::new(SpeedConfig::default());
+        estimator.update(0, 5);
+        // the counter was reset, the delta wraps around
+        estimator.update(1000, 0);
+        assert!(estimator.speed() > 0);
+        estimator.update(2000, u32::MAX);
+        assert!(estimator.speed() > 0);
+        // an overdue pulse brings it back to one pulse per second, 2.1 m/s
+        estimator.update(3000, u32::MAX);
+        assert_eq!(estimator.speed(), 7);
+    }
+
+    #[test]
+    fn bands_have_hysteresis() {
+        let config = SpeedConfig::default();
+        assert_eq!(config.band(0, None), 0);
+        assert_eq!(config.band(15, None), 1);
+        assert_eq!(config.band(21, Some(1)), 1);
+        assert_eq!(config.band(22, Some(1)), 2);
+        assert_eq!(config.band(18, Some(2)), 2);
+        assert_eq!(config.band(17, Some(2)), 1);
+        assert!(!config.high_beam(34, false));
+        assert!(config.high_beam(35, false));
+        assert!(config.high_beam(33, true));
+        assert!(!config.high_beam(32, true));
+    }
+
+    #[test]
+    fn speed_mode_needs_a_wheel_sensor() {
+        with_bench(Setup::default(), &|bench| {
+            assert!(!bench.light_control.set_speed_mode(true));
+            assert!(!bench.light_control.speed_mode());
+        });
+    }
+
+    #[test]
+    fn level_and_high_beam_follow_the_speed() {
+        with_bench(wheel(), &|bench| {
+            assert!(bench.light_control.set_speed_mode(true));
+            bench.ride(15, 5000);
+            assert_eq!(bench.light_control.power_level(), 2);
+            bench.ride(25, 5000);
+            assert_eq!(bench.light_control.power_level(), 3);
+            assert!(!bench.light_control.high_beam());
+            bench.ride(40, 5000);
+            assert_eq!(bench.light_control.power_level(), 4);
+            assert!(bench.light_control.high_beam());
+            bench.ride(25, 5000);
+            assert!(!bench.light_control.high_beam());
+            assert_eq!(bench.light_control.power_level(), 3);
+            bench.ride(0, 5000);
+            assert_eq!(bench.light_control.speed(), 0);
+            assert_eq!(bench.light_control.power_level(), 1);
+        });
+    }
+
+    #[test]
+    fn rider_overrides_until_the_next_band() {
+        with_bench(wheel(), &|bench| {
+            assert!(bench.light_control.set_speed_mode(true));
+            bench.ride(25, 5000);
+            bench.click(bench.plus);
+            bench.advance_time(1000);
+            assert_eq!(bench.light_control.power_level(), 4);
+            bench.ride(26, 5000);
+            assert_eq!(bench.light_control.power_level(), 4);
+            bench.ride(15, 5000);
+            assert_eq!(bench.light_control.power_level(), 2);
+        });
+    }
+
+    #[test]
+    fn manual_level_leaves_the_speed_mode() {
+        with_bench(wheel(), &|bench| {
+            assert!(bench.light_control.set_speed_mode(true));
+            bench.ride(25, 5000);
+            assert!(bench.light_control.set_power_level(1));
+            assert!(!bench.light_control.speed_mode());
+            bench.ride(40, 5000);
+            assert!(bench.light_control.speed().abs_diff(40) <= 2);
+            assert_eq!(bench.light_control.power_level(), 1);
+            assert!(!bench.light_control.high_beam());
+        });
+    }
+
+    fn wheel() -> Setup<'static> {
+        Setup {
+            wheel: true,
+            ..Setup::default()
+        }
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use light_control::telemetry::{Frame, FrameDecoder, FRAME_SIZE};
+
+    const FRAME: Frame = Frame {
+        timestamp: 0x1234_5678,
+        low: 65,
+        high: 85,
+        throttle: 100,
+        battery_mv: 8123,
+        capacity: 87,
+        temp: -12,
+        rgb: 0x05,
+        queue_len: 7,
+    };
+
+    #[test]
+    fn frame_is_decoded() {
+        let encoded = FRAME.encode();
+        assert_eq!(encoded.len(), FRAME_SIZE);
+        assert_eq!(Frame::decode(&encoded), Some(FRAME));
+    }
+
+    #[test]
+    fn corrupted_frame_is_rejected() {
+        let mut encoded = FRAME.encode();
+        encoded[8] ^= 0x10;
+        assert_eq!(Frame::decode(&encoded), None);
+    }
+
+    #[test]
+    fn frames_are_found_between_console_output() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"ok\n\xa5");
+        stream.extend_from_slice(&FRAME.encode());
+        stream.extend_from_slice(b"level\t3\n");
+        let second = Frame {
+            timestamp: 100,
+            ..FRAME
+        };
+        stream.extend_from_slice(&second.encode());
+
+        let mut decoder = FrameDecoder::create();
+        let frames: Vec<Frame> = stream.iter().filter_map(|&b| decoder.push(b)).collect();
+        assert_eq!(frames, vec![FRAME, second]);
+    }
+
+    #[test]
+    fn decoder_recovers_from_truncated_frame() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&FRAME.encode()[..10]);
+        stream.extend_from_slice(&FRAME.encode());
+
+        let mut decoder = FrameDecoder::create();
+        let frames: Vec<Frame> = stream.iter().filter_map(|&b| decoder.push(b)).collect();
+        assert_eq!(frames, vec![FRAME]);
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use light_control::bsp::adc::Decidegrees;
+    use light_control::control::BATTERY_CHECK_PERIOD;
+    use light_control::gestures::LONG_CLICK_THRESHOLD;
+    use light_control::thermal::{ThermalConfig, ThermalRegulator};
+
+    use crate::common::{with_bench, Setup};
+
+    #[test]
+    fn full_output_below_target() {
+        let regulator = ThermalRegulator::new(ThermalConfig::default());
+        for temp in 20..60 {
+            assert_eq!(regulator.update(temp), 100);
+        }
+    }
+
+    #[test]
+    fn output_changes_are_rate_limited() {
+        let regulator = ThermalRegulator::new(ThermalConfig::default());
+        regulator.update(50);
+        assert_eq!(regulator.update(80), 95);
+        assert_eq!(regulator.update(80), 90);
+    }
+
+    #[test]
+    fn output_is_cut_above_trip_until_cooled_down() {
+        let config = ThermalConfig::default();
+        let regulator = ThermalRegulator::new(config);
+        let output = regulator.update(70);
+        assert_eq!(regulator.update(config.trip), output);
+        assert_eq!(regulator.update(config.trip), 0);
+        assert!(regulator.is_tripped());
+        assert_eq!(regulator.update(config.target), 0);
+        // ramps up from the min output
+        assert_eq!(
+            regulator.update(config.target - 1),
+            config.min_output + config.max_step
+        );
+        assert!(!regulator.is_tripped());
+    }
+
+    #[test]
+    fn single_reading_above_trip_is_ignored() {
+        let regulator = ThermalRegulator::new(ThermalConfig::default());
+        regulator.update(50);
+        assert_eq!(regulator.update(110), 100);
+        assert!(!regulator.is_tripped());
+        // the spike does not leak into the derivative either
+        assert_eq!(regulator.update(50), 100);
+        assert_eq!(regulator.update(110), 100);
+        assert!(!regulator.is_tripped());
+    }
+
+    #[test]
+    fn minus_hold_on_the_sensor_wire_does_not_cut_the_output() {
+        with_bench(Setup::default(), &|bench| {
+            let output = bench.low_beam.get();
+            // the button pulls the shared wire down, as on the STM32 board
+            bench.temp.set(Ok(Decidegrees::from_celsius(120)));
+            bench.minus.set(true);
+            bench.advance_time(LONG_CLICK_THRESHOLD + 2 * BATTERY_CHECK_PERIOD);
+            assert_eq!(bench.light_control.throttle(), 100);
+            assert_eq!(bench.low_beam.get(), output);
+
+            bench.temp.set(Ok(Decidegrees::from_celsius(20)));
+            bench.minus.set(false);
+            bench.advance_time(2 * BATTERY_CHECK_PERIOD);
+            assert_eq!(bench.light_control.throttle(), 100);
+            assert_eq!(bench.low_beam.get(), output);
+        });
+    }
+
+    #[test]
+    fn integral_does_not_wind_up_at_min_output() {
+        let config = ThermalConfig::default();
+        let regulator = ThermalRegulator::new(config);
+        for _ in 0..1000 {
+            regulator.update(80);
+        }
+        assert_eq!(regulator.output(), config.min_output);
+
+        // full output is back as fast as the rate limit allows
+        let steps = (0..100)
+            .position(|_| regulator.update(config.target - 10) == 100)
+            .unwrap();
+        assert!(steps <= 20, "recovered after {} steps", steps);
+    }
+
+    /// Both beams at 85%, same model as `power_dissipation` of console_sim
+    #[test]
+    fn temperature_settles_at_target_without_pumping() {
+        let config = ThermalConfig::default();
+        let regulator = ThermalRegulator::new(config);
+        let mut temp = 20;
+        let mut outputs = vec![];
+        // 10 minutes, regulator runs every 500 ms, the model every 250 ms
+        for _ in 0..1200 {
+            let output = regulator.update(temp);
+            outputs.push(output);
+            let led = 85 * output / 100;
+            temp = calculate_temperature(led, led, temp);
+            temp = calculate_temperature(led, led, temp);
+            assert!(temp < config.trip);
+        }
+
+        assert!(
+            (config.target - 2..=config.target).contains(&temp),
+            "{}",
+            temp
+        );
+        let last_minute = &outputs[outputs.len() - 120..];
+        let min = last_minute.iter().min().unwrap();
+        let max = last_minute.iter().max().unwrap();
+        assert!(max - min <= 5, "output pumps between {} and {}", min, max);
+    }
+
+    fn calculate_temperature(led_low: u32, led_high: u32, prev_temp: i32) -> i32 {
+        let prev_temp = prev_temp as f64;
+        let led_low = (led_low as f64) / 100.0;
+        let led_high = (led_high as f64) / 100.0;
+        let efficiency = 0.3;
+        let max_power = 16f64;
+        let ambient = 20.0;
+        let wind_coefficent = 0.3;
+        let generated_power = (1.0 - efficiency) * max_power * (led_low + led_high);
+        let dissipated_power = wind_coefficent * (prev_temp - ambient);
+        let diff = generated_power - dissipated_power;
+        let new_temp: f64 = prev_temp + diff * 1.0;
+        new_temp as i32
+    }
+}
@@ -1,6 +1,7 @@
 MEMORY
 {
   /* STM32G031K8 memory layout */
-  FLASH : ORIGIN = 0x8000000, LENGTH = 64K
+  /* last 2 pages (4K) are reserved for settings, see stm32-nucleo/src/flash.rs */
+  FLASH : ORIGIN = 0x8000000, LENGTH = 60K
   RAM : ORIGIN = 0x20000000, LENGTH = 8K
 }
@@ -0,0 +1,93 @@
+//! Sensors which replay recordings on the host, for the simulator and the tests
+
+use std::cell::Cell;
+
+use light_control::bsp::adc::SensorError;
+use light_control::bsp::ambient::{AmbientLight, Lux};
+use light_control::bsp::motion::{Acceleration, Accelerometer};
+
+/// Samples of a recording with the time in ms in the first column, parsed once.
+/// Lines which do not parse, like a header, are skipped.
+struct Recording<'a, T> {
+    samples: Vec<(u32, T)>,
+    /// Index of the first sample which is not due yet, time only goes forward
+    next: Cell<usize>,
+    /// Current time in ms, the last sample up to it is returned
+    now: &'a dyn Fn() -> u32,
+}
+
+impl<'a, T: Copy> Recording<'a, T> {
+    fn parse(csv: &str, now: &'a dyn Fn() -> u32, parse: fn(&str) -> Option<(u32, T)>) -> Self {
+        Recording {
+            samples: csv.lines().filter_map(parse).collect(),
+            next: Cell::new(0),
+            now,
+        }
+    }
+
+    /// Fails before the first sample, the last sample is kept after the recording ends
+    fn sample(&self) -> Result<T, SensorError> {
+        let now = (self.now)();
+        let mut next = self.next.get();
+        while next < self.samples.len() && self.samples[next].0 <= now {
+            next += 1;
+        }
+        self.next.set(next);
+        next.checked_sub(1)
+            .map(|i| self.samples[i].1)
+            .ok_or(SensorError::Adc)
+    }
+}
+
+/// Replays a recording of `time,x,y,z` lines with the acceleration in mg
+pub struct CsvAccelerometer<'a> {
+    recording: Recording<'a, Acceleration>,
+}
+
+impl<'a> CsvAccelerometer<'a> {
+    pub fn create(csv: &str, now: &'a dyn Fn() -> u32) -> Self {
+        CsvAccelerometer {
+            recording: Recording::parse(csv, now, |line| {
+                let mut fields = line.split(',').map(|it| it.trim());
+                let time = fields.next()?.parse().ok()?;
+                let mut axis = || fields.next()?.parse::<i32>().ok();
+                let acceleration = Acceleration {
+                    x: axis()?,
+                    y: axis()?,
+                    z: axis()?,
+                };
+                Some((time, acceleration))
+            }),
+        }
+    }
+}
+
+impl Accelerometer for CsvAccelerometer<'_> {
+    fn acceleration(&self) -> Result<Acceleration, SensorError> {
+        self.recording.sample()
+    }
+}
+
+/// Replays a recording of `time,lux` lines
+pub struct CsvLightSensor<'a> {
+    recording: Recording<'a, Lux>,
+}
+
+impl<'a> CsvLightSensor<'a> {
+    pub fn create(csv: &str, now: &'a dyn Fn() -> u32) -> Self {
+        CsvLightSensor {
+            recording: Recording::parse(csv, now, |line| {
+                let mut fields = line.split(',').map(|it| it.trim());
+                let time = fields.next()?.parse().ok()?;
+                let lux = fields.next()?.parse().ok()?;
+                Some((time, Lux(lux)))
+            }),
+        }
+    }
+}
+
+impl AmbientLight for CsvLightSensor<'_> {
+    fn lux(&self) -> Result<Lux, SensorError> {
+        self.recording.sample()
+    }
+}
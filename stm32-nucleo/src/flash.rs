@@ -0,0 +1,116 @@
+use core::ptr::{read_volatile, write_volatile};
+
+use light_control::bsp::storage::{FlashPages, StorageError};
+
+/// STM32G031K8 has 32 pages of 2K, last two are excluded from FLASH in memory.x
+pub const STORAGE_FIRST_PAGE: usize = 30;
+pub const STORAGE_PAGES: usize = 2;
+
+const FLASH_BASE: usize = 0x0800_0000;
+const PAGE_SIZE: usize = 2048;
+
+// RM0444 3.7 FLASH registers
+const FLASH_KEYR: *mut u32 = 0x4002_2008 as *mut u32;
+const FLASH_SR: *mut u32 = 0x4002_2010 as *mut u32;
+const FLASH_CR: *mut u32 = 0x4002_2014 as *mut u32;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xcdef_89ab;
+
+const CR_PG: u32 = 1 << 0;
+const CR_PER: u32 = 1 << 1;
+const CR_PNB_SHIFT: u32 = 3;
+const CR_STRT: u32 = 1 << 16;
+const CR_LOCK: u32 = 1 << 31;
+
+const SR_EOP: u32 = 1 << 0;
+/// PROGERR, WRPERR, PGAERR, SIZERR, PGSERR, MISSERR, FASTERR, OPTVERR
+const SR_ERRORS: u32 = 0b111_1111 << 3 | 1 << 15;
+const SR_BSY1: u32 = 1 << 16;
+
+/// Pages of the internal flash used by [RecordStore]
+pub struct G0Flash {
+    pub first_page: usize,
+    pub pages: usize,
+}
+
+impl G0Flash {
+    fn address(&self, page: usize, offset: usize) -> usize {
+        FLASH_BASE + (self.first_page + page) * PAGE_SIZE + offset
+    }
+
+    /// Unlocks the flash, runs [block] and locks the flash again
+    fn unlocked<F: FnOnce() -> Result<(), StorageError>>(
+        &self,
+        block: F,
+    ) -> Result<(), StorageError> {
+        unsafe {
+            wait_not_busy();
+            // clear errors of previous operations
+            write_volatile(FLASH_SR, SR_ERRORS | SR_EOP);
+            if read_volatile(FLASH_CR) & CR_LOCK != 0 {
+                write_volatile(FLASH_KEYR, KEY1);
+                write_volatile(FLASH_KEYR, KEY2);
+            }
+            let result = block();
+            write_volatile(FLASH_CR, CR_LOCK);
+            result
+        }
+    }
+}
+
+impl FlashPages for G0Flash {
+    fn page_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn page_count(&self) -> usize {
+        self.pages
+    }
+
+    fn read(&self, page: usize, offset: usize, buf: &mut [u8]) {
+        let address = self.address(page, offset);
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = unsafe { read_volatile((address + i) as *const u8) };
+        }
+    }
+
+    /// Programs double words, which is the smallest unit of the G0 flash
+    fn write(&self, page: usize, offset: usize, data: &[u8]) -> Result<(), StorageError> {
+        let address = self.address(page, offset);
+        self.unlocked(|| unsafe {
+            write_volatile(FLASH_CR, CR_PG);
+            for (i, chunk) in data.chunks(8).enumerate() {
+                let target = (address + i * 8) as *mut u32;
+                let low = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                // both words have to be written back to back
+                write_volatile(target, low);
+                write_volatile(target.add(1), high);
+                wait_not_busy();
+                if read_volatile(FLASH_SR) & SR_ERRORS != 0 {
+                    return Err(StorageError::Flash);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn erase(&self, page: usize) -> Result<(), StorageError> {
+        let pnb = ((self.first_page + page) as u32) << CR_PNB_SHIFT;
+        self.unlocked(|| unsafe {
+            write_volatile(FLASH_CR, CR_PER | pnb);
+            write_volatile(FLASH_CR, CR_PER | pnb | CR_STRT);
+            wait_not_busy();
+            if read_volatile(FLASH_SR) & SR_ERRORS != 0 {
+                Err(StorageError::Flash)
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+unsafe fn wait_not_busy() {
+    while read_volatile(FLASH_SR) & SR_BSY1 != 0 {}
+}
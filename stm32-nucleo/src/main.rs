@@ -24,14 +24,18 @@ use light_control::bsp::pin::Pin;
 use light_control::bsp::rgb::Rgb;
 use light_control::control::LightControl;
 use light_control::edt::{Event, EDT};
+use light_control::profile::COMMUTE;
+use light_control::record_store::RecordStore;
 
 use crate::adc::AdcSensors;
 use crate::button::PullUpButton;
+use crate::flash::{G0Flash, STORAGE_FIRST_PAGE, STORAGE_PAGES};
 use crate::pwm_led::PwmLed;
 use crate::rgb::GpioRgb;
 
 mod adc;
 mod button;
+mod flash;
 mod pwm_led;
 mod rgb;
 
@@ -99,6 +103,11 @@ fn main() -> ! {
         r_pull_down: 4790,
     };
 
+    let storage = RecordStore::new(G0Flash {
+        first_page: STORAGE_FIRST_PAGE,
+        pages: STORAGE_PAGES,
+    });
+
     let light_control = LightControl::new(
         PullUpButton {
             pin: d12.into_pull_up_input(),
@@ -112,6 +121,8 @@ fn main() -> ! {
         &rgb,
         &edt,
         &sensors,
+        &storage,
+        COMMUTE,
     );
 
     light_control.start();
@@ -125,7 +136,7 @@ fn main() -> ! {
                 watchdog.feed();
                 light_control.process_message(msg);
                 // if edt.now() > prev_logged_time + 2000 {
-                //     let capacity: u32 = sensors.battery_voltage(led_high.get(), led_low.get());
+                //     let capacity: u32 = sensors.battery_voltage().map_or(0, |it| it.0);
                 //
                 //     writeln!(
                 //         output,
@@ -133,7 +144,7 @@ fn main() -> ! {
                 //         led_high.get(),
                 //         led_low.get(),
                 //         capacity,
-                //         sensors.temp(),
+                //         sensors.temp().map_or(0, |it| it.celsius()),
                 //     )
                 //     .unwrap();
                 //     prev_logged_time = edt.now();
@@ -166,6 +177,6 @@ impl Pin for SensorPin<'_> {
     /// When voltage drops to 0, temperature goes into overdrive
     /// which means the button is pressed
     fn is_down(&self) -> bool {
-        self.sensors.temp() >= 110
+        self.sensors.temp().is_ok_and(|it| it.celsius() >= 110)
     }
 }
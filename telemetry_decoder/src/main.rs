@@ -0,0 +1,94 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+
+use light_control::telemetry::{Frame, FrameDecoder};
+
+const USAGE: &str = "usage: telemetry_decoder [--json] [<file or serial device>]
+
+Decodes telemetry frames from the file, or stdin if no file is given, and prints them
+as CSV or, with --json, as one JSON object per line. Console output between frames is skipped.
+Enable the telemetry with the console command `telemetry <ms>` first.";
+
+const CSV_HEADER: &str = "timestamp,low,high,throttle,battery_mv,capacity,temp,rgb,queue_len";
+
+fn main() -> io::Result<()> {
+    let mut json = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let input: Box<dyn Read> = match path {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin()),
+    };
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if !json {
+        writeln!(out, "{}", CSV_HEADER)?;
+    }
+
+    let mut decoder = FrameDecoder::create();
+    let mut buf = [0u8; 256];
+    let mut input = input;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &byte in &buf[..n] {
+            if let Some(frame) = decoder.push(byte) {
+                if json {
+                    write_json(&mut out, &frame)?;
+                } else {
+                    write_csv(&mut out, &frame)?;
+                }
+            }
+        }
+        // a serial device delivers frames one by one, show them right away
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, frame: &Frame) -> io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{},{},{},{},{},{},{}",
+        frame.timestamp,
+        frame.low,
+        frame.high,
+        frame.throttle,
+        frame.battery_mv,
+        frame.capacity,
+        frame.temp,
+        frame.rgb,
+        frame.queue_len
+    )
+}
+
+fn write_json(out: &mut dyn Write, frame: &Frame) -> io::Result<()> {
+    writeln!(
+        out,
+        "{{\"timestamp\":{},\"low\":{},\"high\":{},\"throttle\":{},\"battery_mv\":{},\"capacity\":{},\"temp\":{},\"rgb\":{},\"queue_len\":{}}}",
+        frame.timestamp,
+        frame.low,
+        frame.high,
+        frame.throttle,
+        frame.battery_mv,
+        frame.capacity,
+        frame.temp,
+        frame.rgb,
+        frame.queue_len
+    )
+}